@@ -1,7 +1,7 @@
 use std::{cmp::Ordering, str::FromStr};
 
 use serde::{Deserialize, Serialize};
-use strum::{EnumString, IntoStaticStr, VariantNames};
+use strum::{EnumIter, EnumString, IntoStaticStr, VariantNames};
 
 #[derive(
     Debug,
@@ -12,6 +12,7 @@ use strum::{EnumString, IntoStaticStr, VariantNames};
     Ord,
     PartialOrd,
     Hash,
+    EnumIter,
     EnumString,
     IntoStaticStr,
     strum::Display,
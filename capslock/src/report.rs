@@ -14,6 +14,50 @@ pub struct Report {
     pub process: Process,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub children: Vec<Process>,
+    /// How the report was produced, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+}
+
+/// Information about how a [`Report`] was produced.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The Cargo build that was analysed, for static analysis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildConfig>,
+}
+
+/// The Cargo options used to build the code that was analysed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildConfig {
+    /// The Cargo profile, such as `dev` or `release`.
+    pub profile: String,
+    /// The target triple, if cross-compiling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// The package that was built, if one was selected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub workspace: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub all_features: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_default_features: bool,
+    /// Whether only the library target was selected.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lib: bool,
+    /// The binary target that was selected, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bin: Option<String>,
+    /// Whether all test targets were selected.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tests: bool,
+    /// Whether all example targets were selected.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub examples: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +65,47 @@ pub struct Edge {
     pub caller: usize,
     pub callee: usize,
     pub location: Option<Location>,
+    /// How the callee was determined, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<EdgeKind>,
+    /// Whether the call may unwind into a landing pad in the caller.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub invoke: bool,
+    /// Under what circumstances the call is made within the caller, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<CallContext>,
+}
+
+/// How the callee of an [`Edge`] was determined by static analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// A call through a function pointer, assumed to target any function with a matching type.
+    Indirect,
+    /// A call through a trait object, resolved to the functions in the matching vtable slot.
+    Vtable,
+    /// A direct call to a named function.
+    Direct,
+}
+
+/// Under what circumstances the call of an [`Edge`] is made within the caller, as determined by
+/// static analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallContext {
+    /// Only while panicking: either during unwinding, or on a path that never returns.
+    Panic,
+    /// Only under some branch.
+    Conditional,
+    /// Whenever the caller returns normally.
+    Unconditional,
+}
+
+impl EdgeKind {
+    /// Returns true if the edge may not correspond to a call that can actually happen.
+    pub fn is_speculative(&self) -> bool {
+        !matches!(self, Self::Direct)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +116,23 @@ pub struct Function {
     pub capabilities: BTreeMap<Capability, CapabilityType>,
     #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
     pub syscalls: BTreeSet<String>,
+    /// The index of the package that defined this function in [`Process::packages`], if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<usize>,
+    /// The call through which the function obtained each of its transitive capabilities, on a
+    /// shortest path to a function with that capability directly.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub witnesses: BTreeMap<Capability, Witness>,
+    /// The call path from the function to a function with each of its transitive capabilities
+    /// directly, as returned by [`Process::path`].
+    ///
+    /// This is only filled in for functions that are reachable from an entry point.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub paths: BTreeMap<Capability, Vec<Witness>>,
+    /// Constant arguments that the function passes to capability-relevant calls, such as the
+    /// program given to `std::process::Command::new`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evidence: Vec<Evidence>,
 }
 
 impl Function {
@@ -59,6 +161,31 @@ impl Function {
     }
 }
 
+/// A call made by a function, which is the first step towards a function with a capability.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Witness {
+    /// The index of the called function in [`Process::functions`].
+    pub callee: usize,
+    /// Where the call is made, if known.
+    pub location: Option<Location>,
+}
+
+/// A constant argument passed to a call that's relevant to a capability.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Evidence {
+    /// The display name of the called function.
+    pub callee: String,
+    pub argument: String,
+    /// Where the call is made, if known.
+    pub location: Option<Location>,
+}
+
+impl Display for Evidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({:?})", self.callee, self.argument)
+    }
+}
+
 impl Display for Function {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.display_name().fmt(f)
@@ -148,7 +275,7 @@ pub enum RustFunctionName {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Location {
     pub directory: Option<PathBuf>,
     pub filename: PathBuf,
@@ -156,12 +283,181 @@ pub struct Location {
     pub column: Option<u64>,
 }
 
+/// A package that code in the report was built from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+}
+
+/// The capabilities used by a single package, split by where they come from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageSummary {
+    /// The index of the package in [`Process::packages`], or `None` for functions that couldn't
+    /// be attributed to a package.
+    pub package: Option<usize>,
+    /// Capabilities directly required by at least one function in the package.
+    pub direct: BTreeSet<Capability>,
+    /// Capabilities that functions in the package only obtain by calling into other packages.
+    pub transitive: BTreeSet<Capability>,
+}
+
+/// The capabilities enabled by each Cargo feature of a package, relative to building it with no
+/// features at all.
+///
+/// Capabilities include those of the build scripts and proc-macros in each build, as well as
+/// those of the program itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureMatrix {
+    /// The package whose features were built.
+    pub package: Package,
+    /// The capabilities found with no features enabled, not even `default`.
+    pub baseline: BTreeSet<Capability>,
+    /// The capabilities added to the baseline by enabling each feature on its own, by feature
+    /// name.
+    pub features: BTreeMap<String, BTreeSet<Capability>>,
+}
+
+/// A function in the public API of a library crate, along with everything calling it can do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiItem {
+    /// The index of the function in [`Process::functions`].
+    pub function: usize,
+    /// The direct and transitive capabilities of the function, excluding safe.
+    pub capabilities: BTreeSet<Capability>,
+    /// The call path from the function to a function with each of its transitive capabilities
+    /// directly, as returned by [`Process::path`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub paths: BTreeMap<Capability, Vec<Witness>>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Process {
     pub path: PathBuf,
+    /// What the process represents, if it's compile-time code rather than the program itself.
+    #[serde(default)]
+    pub role: Option<Role>,
     pub capabilities: BTreeSet<Capability>,
     pub functions: Vec<Function>,
     pub edges: Vec<Edge>,
+    #[serde(default)]
+    pub packages: Vec<Package>,
+    /// Indices of functions with capabilities that aren't reachable from any entry point, and
+    /// therefore don't contribute to [`Process::capabilities`].
+    #[serde(default)]
+    pub unreachable: Vec<usize>,
+    /// The public API of the analysed library crates, if requested.
+    #[serde(default)]
+    pub api: Vec<ApiItem>,
+}
+
+/// The kind of compile-time code that a [`Process`] in a static analysis report represents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Role {
+    /// The build script of a package, which runs before the package is compiled.
+    BuildScript { package: Package },
+    /// A procedural macro crate, which runs within the compiler.
+    ProcMacro { package: Package },
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BuildScript { package } => write!(f, "build script of {}", package.name),
+            Self::ProcMacro { package } => write!(f, "proc-macro {}", package.name),
+        }
+    }
+}
+
+impl Process {
+    /// Returns the calls from the given function to the nearest function that has the given
+    /// capability directly, by following each function's [`Function::witnesses`].
+    ///
+    /// The path is empty if the function has the capability directly, or doesn't have it at all.
+    pub fn path(&self, function: usize, capability: Capability) -> Vec<&Witness> {
+        let mut path = Vec::new();
+        let mut current = function;
+
+        // Witnesses always lead to a direct source, but a malformed report could still contain a
+        // cycle, and no path can be longer than the number of functions.
+        while let Some(witness) = self
+            .functions
+            .get(current)
+            .and_then(|func| func.witnesses.get(&capability))
+            .filter(|_| path.len() < self.functions.len())
+        {
+            path.push(witness);
+            current = witness.callee;
+        }
+
+        path
+    }
+
+    /// Summarises the capabilities used by each package in the process.
+    ///
+    /// Only reachable functions are counted, so the summary agrees with
+    /// [`Process::capabilities`].
+    ///
+    /// Packages are returned in the same order as [`Process::packages`], followed by a summary of
+    /// any functions that couldn't be attributed to a package. If no packages are known, the
+    /// summary is empty.
+    pub fn summary(&self) -> Vec<PackageSummary> {
+        if self.packages.is_empty() {
+            return Vec::new();
+        }
+
+        let mut summaries = (0..self.packages.len())
+            .map(Some)
+            .chain(std::iter::once(None))
+            .map(|package| PackageSummary {
+                package,
+                direct: BTreeSet::new(),
+                transitive: BTreeSet::new(),
+            })
+            .collect::<Vec<_>>();
+        let unattributed = self.packages.len();
+        let unreachable = self.unreachable.iter().collect::<BTreeSet<_>>();
+
+        for (idx, func) in self.functions.iter().enumerate() {
+            if unreachable.contains(&idx) {
+                continue;
+            }
+            let Some(summary) = summaries.get_mut(func.package.unwrap_or(unattributed)) else {
+                continue;
+            };
+
+            for (cap, ty) in func.capabilities.iter() {
+                if cap == &Capability::Safe {
+                    continue;
+                }
+
+                match ty {
+                    CapabilityType::Direct => summary.direct.insert(*cap),
+                    _ => summary.transitive.insert(*cap),
+                };
+            }
+        }
+
+        // Anything with a direct source in the package isn't only inherited from elsewhere.
+        for summary in summaries.iter_mut() {
+            let PackageSummary {
+                direct, transitive, ..
+            } = summary;
+            transitive.retain(|cap| !direct.contains(cap));
+        }
+
+        // Only include the unattributed summary if there's something in it.
+        if summaries
+            .last()
+            .is_some_and(|summary| summary.direct.is_empty() && summary.transitive.is_empty())
+        {
+            summaries.pop();
+        }
+
+        summaries
+    }
 }
 
 impl Serialize for Process {
@@ -172,11 +468,21 @@ impl Serialize for Process {
         #[derive(Serialize)]
         struct Raw<'a> {
             path: &'a Path,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            role: Option<&'a Role>,
             capabilities: BTreeSet<Capability>,
             functions: &'a [Function],
             edges: &'a [Edge],
             #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
             syscalls: BTreeSet<&'a str>,
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            packages: &'a [Package],
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            summary: Vec<PackageSummary>,
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            unreachable: &'a [usize],
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            api: &'a [ApiItem],
         }
 
         // Always remove safe from the top level capability list.
@@ -189,10 +495,15 @@ impl Serialize for Process {
 
         Raw {
             path: &self.path,
+            role: self.role.as_ref(),
             capabilities,
             functions: &self.functions,
             edges: &self.edges,
             syscalls: collect_syscalls(&self.functions),
+            packages: &self.packages,
+            summary: self.summary(),
+            unreachable: &self.unreachable,
+            api: &self.api,
         }
         .serialize(serializer)
     }
@@ -206,3 +517,139 @@ fn collect_syscalls(functions: &[Function]) -> BTreeSet<&str> {
             syscalls
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(
+        name: &str,
+        package: Option<usize>,
+        capabilities: impl IntoIterator<Item = (Capability, CapabilityType)>,
+    ) -> Function {
+        Function {
+            name: FunctionName::Other {
+                display_name: name.to_string(),
+                language: "unknown".to_string(),
+            },
+            location: None,
+            capabilities: capabilities.into_iter().collect(),
+            syscalls: BTreeSet::new(),
+            package,
+            witnesses: BTreeMap::new(),
+            paths: BTreeMap::new(),
+            evidence: Vec::new(),
+        }
+    }
+
+    fn package(name: &str) -> Package {
+        Package {
+            id: format!("{name}@1.0.0"),
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+        }
+    }
+
+    #[test]
+    fn summary() {
+        use CapabilityType::*;
+
+        let process = Process {
+            path: PathBuf::new(),
+            role: None,
+            capabilities: BTreeSet::new(),
+            functions: vec![
+                // `a` uses the network itself, and execs via `b`.
+                function("a::net", Some(0), [(Capability::Network, Direct)]),
+                function(
+                    "a::main",
+                    Some(0),
+                    [
+                        (Capability::Network, Transitive),
+                        (Capability::Exec, Transitive),
+                    ],
+                ),
+                // `b` only execs via an unattributed function.
+                function("b::spawn", Some(1), [(Capability::Exec, Transitive)]),
+                function("exec", None, [(Capability::Exec, Direct)]),
+                // `c` is safe, apart from a function that's never called.
+                function("c::safe", Some(2), [(Capability::Safe, Direct)]),
+                function("c::unused", Some(2), [(Capability::Files, Direct)]),
+            ],
+            edges: Vec::new(),
+            packages: vec![package("a"), package("b"), package("c")],
+            unreachable: vec![5],
+            api: Vec::new(),
+        };
+
+        assert_eq!(
+            process.summary(),
+            vec![
+                PackageSummary {
+                    package: Some(0),
+                    direct: [Capability::Network].into(),
+                    transitive: [Capability::Exec].into(),
+                },
+                PackageSummary {
+                    package: Some(1),
+                    direct: BTreeSet::new(),
+                    transitive: [Capability::Exec].into(),
+                },
+                PackageSummary {
+                    package: Some(2),
+                    direct: BTreeSet::new(),
+                    transitive: BTreeSet::new(),
+                },
+                PackageSummary {
+                    package: None,
+                    direct: [Capability::Exec].into(),
+                    transitive: BTreeSet::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn path() {
+        use CapabilityType::*;
+
+        let witness = |callee, line| Witness {
+            callee,
+            location: Some(Location {
+                directory: None,
+                filename: PathBuf::from("src/lib.rs"),
+                line,
+                column: None,
+            }),
+        };
+        let mut functions = vec![
+            function("main", None, [(Capability::Exec, Transitive)]),
+            function("run", None, [(Capability::Exec, Transitive)]),
+            function("spawn", None, [(Capability::Exec, Direct)]),
+        ];
+        functions[0]
+            .witnesses
+            .insert(Capability::Exec, witness(1, 10));
+        functions[1]
+            .witnesses
+            .insert(Capability::Exec, witness(2, 20));
+
+        let process = Process {
+            path: PathBuf::new(),
+            role: None,
+            capabilities: BTreeSet::new(),
+            functions,
+            edges: Vec::new(),
+            packages: Vec::new(),
+            unreachable: Vec::new(),
+            api: Vec::new(),
+        };
+
+        assert_eq!(
+            process.path(0, Capability::Exec),
+            vec![&witness(1, 10), &witness(2, 20)]
+        );
+        assert!(process.path(2, Capability::Exec).is_empty());
+        assert!(process.path(0, Capability::Network).is_empty());
+    }
+}
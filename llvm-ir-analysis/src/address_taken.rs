@@ -0,0 +1,145 @@
+use either::Either;
+use llvm_ir::instruction::InlineAssembly;
+use llvm_ir::{Constant, Instruction, Module, Name, Operand, Terminator, Type};
+use std::collections::HashSet;
+
+/// The set of functions in the analyzed `Module`(s) whose address is taken,
+/// and which therefore may be the target of an indirect call.
+///
+/// A function's address is considered taken if it appears anywhere other than
+/// as the callee of a direct call: as an instruction operand (for example,
+/// stored to memory, passed as an argument, or returned), or in the
+/// initializer of a global variable or alias.
+///
+/// To construct an `AddressTaken`, use [`ModuleAnalysis`](struct.ModuleAnalysis.html)
+/// or [`CrossModuleAnalysis`](struct.CrossModuleAnalysis.html).
+pub struct AddressTaken<'m> {
+    functions: HashSet<&'m str>,
+}
+
+impl<'m> AddressTaken<'m> {
+    pub(crate) fn new(modules: impl IntoIterator<Item = &'m Module>) -> Self {
+        let mut functions = HashSet::new();
+        for module in modules {
+            for var in &module.global_vars {
+                if let Some(init) = &var.initializer {
+                    referenced_functions(init, &mut functions);
+                }
+            }
+            for alias in &module.global_aliases {
+                referenced_functions(&alias.aliasee, &mut functions);
+            }
+            for f in &module.functions {
+                for bb in &f.basic_blocks {
+                    for inst in &bb.instrs {
+                        for op in value_operands(inst) {
+                            operand_functions(op, &mut functions);
+                        }
+                    }
+                    match &bb.term {
+                        Terminator::Ret(ret) => {
+                            if let Some(op) = &ret.return_operand {
+                                operand_functions(op, &mut functions);
+                            }
+                        }
+                        Terminator::Invoke(invoke) => {
+                            for (op, _) in &invoke.arguments {
+                                operand_functions(op, &mut functions);
+                            }
+                            if let Some(callee) = wrapped_callee(&invoke.function) {
+                                operand_functions(callee, &mut functions);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Self { functions }
+    }
+
+    /// Does the given function have its address taken?
+    pub fn contains(&self, func_name: &str) -> bool {
+        self.functions.contains(func_name)
+    }
+
+    /// Iterate over the names of all the functions whose address is taken.
+    pub fn functions<'s>(&'s self) -> impl Iterator<Item = &'m str> + 's {
+        self.functions.iter().copied()
+    }
+}
+
+/// Operands of the given instruction which may carry a function pointer
+/// somewhere other than the callee position of a call.
+fn value_operands(inst: &Instruction) -> Vec<&Operand> {
+    match inst {
+        Instruction::Store(store) => vec![&store.value],
+        Instruction::Select(select) => vec![&select.true_value, &select.false_value],
+        Instruction::Phi(phi) => phi.incoming_values.iter().map(|(op, _)| op).collect(),
+        Instruction::InsertValue(iv) => vec![&iv.element],
+        Instruction::InsertElement(ie) => vec![&ie.element],
+        Instruction::CmpXchg(cx) => vec![&cx.replacement],
+        Instruction::AtomicRMW(rmw) => vec![&rmw.value],
+        Instruction::BitCast(bc) => vec![&bc.operand],
+        Instruction::AddrSpaceCast(asc) => vec![&asc.operand],
+        Instruction::PtrToInt(pti) => vec![&pti.operand],
+        Instruction::GetElementPtr(gep) => vec![&gep.address],
+        Instruction::Call(call) => {
+            let mut ops: Vec<&Operand> = call.arguments.iter().map(|(op, _)| op).collect();
+            ops.extend(wrapped_callee(&call.function));
+            ops
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// A callee which is a constant expression wrapping a function (for example,
+/// a cast) rather than a plain reference to it. The call graph treats these
+/// like any other function pointer, so they need to count as address-taken.
+fn wrapped_callee(callee: &Either<InlineAssembly, Operand>) -> Option<&Operand> {
+    match callee {
+        Either::Right(op @ Operand::ConstantOperand(cref))
+            if !matches!(cref.as_ref(), Constant::GlobalReference { .. }) =>
+        {
+            Some(op)
+        }
+        _ => None,
+    }
+}
+
+fn operand_functions<'m>(op: &'m Operand, out: &mut HashSet<&'m str>) {
+    if let Operand::ConstantOperand(cref) = op {
+        referenced_functions(cref, out);
+    }
+}
+
+/// Collects the names of all the functions referenced by the given constant,
+/// such as a global variable's initializer.
+pub fn referenced_functions<'m>(constant: &'m Constant, out: &mut HashSet<&'m str>) {
+    match constant {
+        Constant::GlobalReference {
+            name: Name::Name(name),
+            ty,
+        } => {
+            if let Type::FuncType { .. } = ty.as_ref() {
+                out.insert(name);
+            }
+        }
+        Constant::Struct { values, .. } => {
+            for value in values {
+                referenced_functions(value, out);
+            }
+        }
+        Constant::Array { elements, .. } | Constant::Vector(elements) => {
+            for element in elements {
+                referenced_functions(element, out);
+            }
+        }
+        Constant::BitCast(cast) => referenced_functions(&cast.operand, out),
+        Constant::AddrSpaceCast(cast) => referenced_functions(&cast.operand, out),
+        Constant::PtrToInt(cast) => referenced_functions(&cast.operand, out),
+        Constant::IntToPtr(cast) => referenced_functions(&cast.operand, out),
+        Constant::GetElementPtr(gep) => referenced_functions(&gep.address, out),
+        _ => {}
+    }
+}
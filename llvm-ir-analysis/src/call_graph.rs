@@ -1,4 +1,6 @@
+use crate::address_taken::AddressTaken;
 use crate::functions_by_type::FunctionsByType;
+use crate::vtables::{self, LocalDefs, Vtables};
 use either::Either;
 use llvm_ir::{
     instruction::{Call, InlineAssembly},
@@ -18,6 +20,19 @@ pub struct CallGraph<'m> {
     graph: DiGraphMap<&'m str, CallOrInvoke<'m>>,
 }
 
+/// Options controlling how a [`CallGraph`](struct.CallGraph.html) resolves
+/// indirect calls.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CallGraphOptions {
+    /// Only link indirect calls to functions whose address is taken somewhere
+    /// in the analyzed `Module`(s), as determined by
+    /// [`AddressTaken`](struct.AddressTaken.html).
+    ///
+    /// By default, indirect calls are conservatively linked to every function
+    /// with the appropriate type.
+    pub address_taken_only: bool,
+}
+
 impl<'m> CallGraph<'m> {
     pub fn inner(&self) -> &DiGraphMap<&'m str, CallOrInvoke<'m>> {
         &self.graph
@@ -26,10 +41,65 @@ impl<'m> CallGraph<'m> {
     pub(crate) fn new(
         modules: impl IntoIterator<Item = &'m Module>,
         functions_by_type: &FunctionsByType<'m>,
+        vtables: &Vtables<'m>,
+        address_taken: Option<&AddressTaken<'m>>,
     ) -> Self {
         let mut graph: DiGraphMap<&'m str, CallOrInvoke<'m>> = DiGraphMap::new();
 
+        // The functions with the given type that a function pointer may point
+        // to.
+        let functions_with_type = |ty: &TypeRef| -> Vec<&'m str> {
+            functions_by_type
+                .functions_with_type(ty)
+                .filter(|f| address_taken.map_or(true, |at| at.contains(f)))
+                .collect()
+        };
+
+        // The possible targets of a call through a function pointer, along
+        // with how they were determined.
+        let indirect_targets = |module: &'m Module,
+                                defs: &LocalDefs<'m>,
+                                callee: &Operand,
+                                callee_ty: &TypeRef|
+         -> (Vec<&'m str>, CallResolution) {
+            // Unless the function pointer was loaded from a vtable, assume
+            // that it could point to any function in the analyzed module(s)
+            // that has the appropriate type
+            let Some(slot) = vtables::method_slot(module, defs, callee) else {
+                return (functions_with_type(callee_ty), CallResolution::Indirect);
+            };
+
+            // If the function pointer was loaded from a vtable, the call can
+            // only target functions stored at the same offset in some vtable.
+            let mut targets: Vec<&'m str> = vtables
+                .functions_at_offset(slot.offset, callee_ty)
+                .collect();
+            if slot.is_dyn_metadata && !targets.is_empty() {
+                return (targets, CallResolution::Vtable);
+            }
+
+            // If it may have been loaded from something else at the same
+            // offset, such as a struct of callbacks, it may point to either.
+            targets.extend(functions_with_type(callee_ty));
+            targets.sort_unstable();
+            targets.dedup();
+            (targets, CallResolution::Indirect)
+        };
+
+        // Add an edge, unless there's already an edge between the same
+        // functions that was resolved with more certainty.
+        let add_edge = |graph: &mut DiGraphMap<&'m str, CallOrInvoke<'m>>,
+                        caller: &'m str,
+                        callee: &'m str,
+                        call: CallOrInvoke<'m>| {
+            let existing = graph.edge_weight(caller, callee);
+            if existing.map_or(true, |existing| existing.resolution() <= call.resolution()) {
+                graph.add_edge(caller, callee, call);
+            }
+        };
+
         let add_edge_for_call = |graph: &mut DiGraphMap<_, _>,
+                                 defs: &LocalDefs<'m>,
                                  caller: &'m str,
                                  call: CallOrInvoke<'m>| {
             match call.callee() {
@@ -39,7 +109,7 @@ impl<'m> CallGraph<'m> {
                             name: Name::Name(name),
                             ..
                         } => {
-                            graph.add_edge(caller, name, call);
+                            add_edge(graph, caller, name, call);
                         }
                         Constant::GlobalReference { name, .. } => {
                             unimplemented!("Call of a function with a numbered name: {name:?}")
@@ -49,18 +119,19 @@ impl<'m> CallGraph<'m> {
                             // Assume that this function pointer could point
                             // to any function in the current module that has
                             // the appropriate type
-                            for target in functions_by_type.functions_with_type(&call.callee_ty()) {
-                                graph.add_edge(caller, target, call.clone());
+                            let call = call.with_resolution(CallResolution::Indirect);
+                            for target in functions_with_type(&call.callee_ty()) {
+                                add_edge(graph, caller, target, call.clone());
                             }
                         }
                     }
                 }
-                Either::Right(_) => {
-                    // Assume that this function pointer could point to any
-                    // function in the current module that has the
-                    // appropriate type
-                    for target in functions_by_type.functions_with_type(&call.callee_ty()) {
-                        graph.add_edge(caller, target, call.clone());
+                Either::Right(callee) => {
+                    let (targets, resolution) =
+                        indirect_targets(call.module(), defs, callee, &call.callee_ty());
+                    let call = call.with_resolution(resolution);
+                    for target in targets {
+                        add_edge(graph, caller, target, call.clone());
                     }
                 }
                 Either::Left(_) => {} // ignore calls to inline assembly
@@ -71,21 +142,32 @@ impl<'m> CallGraph<'m> {
         for module in modules {
             for f in &module.functions {
                 graph.add_node(&f.name); // just to ensure all functions end up getting nodes in the graph by the end
+                let defs = LocalDefs::new(f);
                 for bb in &f.basic_blocks {
                     for inst in &bb.instrs {
                         if let Instruction::Call(call) = inst {
                             add_edge_for_call(
                                 &mut graph,
+                                &defs,
                                 &f.name,
-                                CallOrInvoke::Call { call, module },
+                                CallOrInvoke::Call {
+                                    module,
+                                    call,
+                                    resolution: CallResolution::Direct,
+                                },
                             );
                         }
                     }
                     if let Terminator::Invoke(invoke) = &bb.term {
                         add_edge_for_call(
                             &mut graph,
+                            &defs,
                             &f.name,
-                            CallOrInvoke::Invoke { invoke, module },
+                            CallOrInvoke::Invoke {
+                                module,
+                                invoke,
+                                resolution: CallResolution::Direct,
+                            },
                         );
                     }
                 }
@@ -99,7 +181,10 @@ impl<'m> CallGraph<'m> {
     /// given function.
     ///
     /// This analysis conservatively assumes that function pointers may point to
-    /// any function in the analyzed `Module`(s) that has the appropriate type.
+    /// any function in the analyzed `Module`(s) that has the appropriate type
+    /// (and, if [`CallGraphOptions::address_taken_only`] is set, whose address
+    /// is taken), except for those known to be loaded from a vtable, which may
+    /// only point to the functions stored at the same offset in a vtable.
     ///
     /// Panics if the given function is not found in the analyzed `Module`(s).
     pub fn callers<'s>(&'s self, func_name: &'m str) -> impl Iterator<Item = &'m str> + 's {
@@ -117,7 +202,10 @@ impl<'m> CallGraph<'m> {
     /// called by the given function.
     ///
     /// This analysis conservatively assumes that function pointers may point to
-    /// any function in the analyzed `Module`(s) that has the appropriate type.
+    /// any function in the analyzed `Module`(s) that has the appropriate type
+    /// (and, if [`CallGraphOptions::address_taken_only`] is set, whose address
+    /// is taken), except for those known to be loaded from a vtable, which may
+    /// only point to the functions stored at the same offset in a vtable.
     ///
     /// Panics if the given function is not found in the analyzed `Module`(s).
     pub fn callees<'s>(&'s self, func_name: &'m str) -> impl Iterator<Item = &'m str> + 's {
@@ -132,22 +220,66 @@ impl<'m> CallGraph<'m> {
     }
 }
 
+/// How the target of a [`CallOrInvoke`](enum.CallOrInvoke.html) edge in the
+/// call graph was determined.
+///
+/// Resolutions are ordered from least to most certain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CallResolution {
+    /// A call through a function pointer, assumed to target any function with
+    /// the appropriate type.
+    Indirect,
+    /// A call through a function pointer loaded from a vtable, resolved to the
+    /// functions stored at the same offset in a vtable.
+    Vtable,
+    /// A direct call to a named function.
+    Direct,
+}
+
 #[derive(Clone)]
 pub enum CallOrInvoke<'a> {
     Call {
-        #[cfg_attr(feature = "llvm-15-or-greater", allow(dead_code))]
         module: &'a Module,
         call: &'a Call,
+        resolution: CallResolution,
     },
     Invoke {
-        #[cfg_attr(feature = "llvm-15-or-greater", allow(dead_code))]
         module: &'a Module,
         invoke: &'a Invoke,
+        resolution: CallResolution,
     },
 }
 
 impl<'a> CallOrInvoke<'a> {
-    #[cfg(feature = "llvm-14-or-lower")]
+    /// How the target of this call was determined.
+    pub fn resolution(&self) -> CallResolution {
+        match self {
+            Self::Call { resolution, .. } => *resolution,
+            Self::Invoke { resolution, .. } => *resolution,
+        }
+    }
+
+    /// Is this an `invoke`, which may unwind to a landing pad, rather than a
+    /// plain `call`?
+    pub fn is_invoke(&self) -> bool {
+        matches!(self, Self::Invoke { .. })
+    }
+
+    fn with_resolution(self, resolution: CallResolution) -> Self {
+        match self {
+            Self::Call { module, call, .. } => Self::Call {
+                module,
+                call,
+                resolution,
+            },
+            Self::Invoke { module, invoke, .. } => Self::Invoke {
+                module,
+                invoke,
+                resolution,
+            },
+        }
+    }
+
     fn module(&self) -> &'a Module {
         match self {
             Self::Call { module, .. } => module,
@@ -162,14 +294,14 @@ impl<'a> CallOrInvoke<'a> {
         }
     }
 
-    fn callee(&self) -> &'a Either<InlineAssembly, Operand> {
+    pub(crate) fn callee(&self) -> &'a Either<InlineAssembly, Operand> {
         match self {
             Self::Call { call, .. } => &call.function,
             Self::Invoke { invoke, .. } => &invoke.function,
         }
     }
 
-    fn callee_ty(&self) -> TypeRef {
+    pub(crate) fn callee_ty(&self) -> TypeRef {
         #[cfg(feature = "llvm-14-or-lower")]
         match self.module().type_of(self.callee()).as_ref() {
             llvm_ir::Type::PointerType { pointee_type, .. } => pointee_type.clone(),
@@ -4,17 +4,26 @@
 //! For a more thorough introduction to the crate and how to get started,
 //! see the [crate's README](https://github.com/cdisselkoen/llvm-ir-analysis/blob/main/README.md).
 
+mod address_taken;
 mod call_graph;
 mod control_dep_graph;
 mod control_flow_graph;
 mod dominator_tree;
 mod functions_by_type;
+mod summary;
+mod vtables;
 
-pub use crate::call_graph::CallGraph;
+pub use crate::address_taken::{referenced_functions, AddressTaken};
+pub use crate::call_graph::{CallGraph, CallGraphOptions, CallOrInvoke, CallResolution};
 pub use crate::control_dep_graph::ControlDependenceGraph;
 pub use crate::control_flow_graph::{CFGNode, ControlFlowGraph};
 pub use crate::dominator_tree::{DominatorTree, PostDominatorTree};
 pub use crate::functions_by_type::FunctionsByType;
+pub use crate::summary::{
+    CallContext, CallSummary, CalleeSummary, FunctionSummary, ModuleSummary, SummaryCall,
+    SummaryCallGraph, VtableSlot,
+};
+pub use crate::vtables::Vtables;
 use llvm_ir::{Function, Module};
 use log::debug;
 use std::cell::{Ref, RefCell};
@@ -24,15 +33,62 @@ use std::collections::HashMap;
 // have to worry about matching versions.
 pub use llvm_ir;
 
+/// The major version of LLVM that this crate was built to analyze, as
+/// selected with the `llvm-x` features.
+///
+/// Bitcode produced by a newer LLVM generally can't be parsed.
+#[cfg(feature = "llvm-9")]
+pub const LLVM_VERSION_MAJOR: u32 = 9;
+
+#[cfg(feature = "llvm-10")]
+pub const LLVM_VERSION_MAJOR: u32 = 10;
+
+#[cfg(feature = "llvm-11")]
+pub const LLVM_VERSION_MAJOR: u32 = 11;
+
+#[cfg(feature = "llvm-12")]
+pub const LLVM_VERSION_MAJOR: u32 = 12;
+
+#[cfg(feature = "llvm-13")]
+pub const LLVM_VERSION_MAJOR: u32 = 13;
+
+#[cfg(feature = "llvm-14")]
+pub const LLVM_VERSION_MAJOR: u32 = 14;
+
+#[cfg(feature = "llvm-15")]
+pub const LLVM_VERSION_MAJOR: u32 = 15;
+
+#[cfg(feature = "llvm-16")]
+pub const LLVM_VERSION_MAJOR: u32 = 16;
+
+#[cfg(feature = "llvm-17")]
+pub const LLVM_VERSION_MAJOR: u32 = 17;
+
+#[cfg(feature = "llvm-18")]
+pub const LLVM_VERSION_MAJOR: u32 = 18;
+
+#[cfg(feature = "llvm-19")]
+pub const LLVM_VERSION_MAJOR: u32 = 19;
+
+#[cfg(feature = "llvm-20")]
+pub const LLVM_VERSION_MAJOR: u32 = 20;
+
 /// Computes (and caches the results of) various analyses on a given `Module`
 pub struct ModuleAnalysis<'m> {
     /// Reference to the `llvm-ir` `Module`
     module: &'m Module,
+    /// Options for computing the call graph
+    call_graph_options: CallGraphOptions,
     /// Call graph for the module
     call_graph: SimpleCache<CallGraph<'m>>,
+    /// Functions whose address is taken within the module
+    address_taken: SimpleCache<AddressTaken<'m>>,
     /// `FunctionsByType`, which allows you to iterate over the module's
     /// functions by type
     functions_by_type: SimpleCache<FunctionsByType<'m>>,
+    /// `Vtables`, which allows you to iterate over the functions stored in the
+    /// module's vtables
+    vtables: SimpleCache<Vtables<'m>>,
     /// Map from function name to the `FunctionAnalysis` for that function
     fn_analyses: HashMap<&'m str, FunctionAnalysis<'m>>,
 }
@@ -45,8 +101,11 @@ impl<'m> ModuleAnalysis<'m> {
     pub fn new(module: &'m Module) -> Self {
         Self {
             module,
+            call_graph_options: CallGraphOptions::default(),
             call_graph: SimpleCache::new(),
+            address_taken: SimpleCache::new(),
             functions_by_type: SimpleCache::new(),
+            vtables: SimpleCache::new(),
             fn_analyses: module
                 .functions
                 .iter()
@@ -55,6 +114,14 @@ impl<'m> ModuleAnalysis<'m> {
         }
     }
 
+    /// Set the options used when computing the `CallGraph` for the `Module`.
+    pub fn with_call_graph_options(self, call_graph_options: CallGraphOptions) -> Self {
+        Self {
+            call_graph_options,
+            ..self
+        }
+    }
+
     /// Get a reference to the `Module` which the `ModuleAnalysis` was created
     /// with.
     pub fn module(&self) -> &'m Module {
@@ -65,8 +132,26 @@ impl<'m> ModuleAnalysis<'m> {
     pub fn call_graph(&self) -> Ref<CallGraph<'m>> {
         self.call_graph.get_or_insert_with(|| {
             let functions_by_type = self.functions_by_type();
+            let vtables = self.vtables();
+            let address_taken = self
+                .call_graph_options
+                .address_taken_only
+                .then(|| self.address_taken());
             debug!("computing single-module call graph");
-            CallGraph::new(std::iter::once(self.module), &functions_by_type)
+            CallGraph::new(
+                std::iter::once(self.module),
+                &functions_by_type,
+                &vtables,
+                address_taken.as_deref(),
+            )
+        })
+    }
+
+    /// Get the `AddressTaken` for the `Module`.
+    pub fn address_taken(&self) -> Ref<AddressTaken<'m>> {
+        self.address_taken.get_or_insert_with(|| {
+            debug!("computing single-module address-taken functions");
+            AddressTaken::new(std::iter::once(self.module))
         })
     }
 
@@ -78,6 +163,14 @@ impl<'m> ModuleAnalysis<'m> {
         })
     }
 
+    /// Get the `Vtables` for the `Module`.
+    pub fn vtables(&self) -> Ref<Vtables<'m>> {
+        self.vtables.get_or_insert_with(|| {
+            debug!("computing single-module vtables");
+            Vtables::new(std::iter::once(self.module))
+        })
+    }
+
     /// Get the `FunctionAnalysis` for the function with the given name.
     ///
     /// Panics if no function of that name exists in the `Module` which the
@@ -95,10 +188,17 @@ impl<'m> ModuleAnalysis<'m> {
 pub struct CrossModuleAnalysis<'m> {
     /// Reference to the `llvm-ir` `Module`s
     modules: Vec<&'m Module>,
+    /// Options for computing the call graph
+    call_graph_options: CallGraphOptions,
     /// Cross-module call graph
     call_graph: SimpleCache<CallGraph<'m>>,
+    /// Functions whose address is taken within any of the modules
+    address_taken: SimpleCache<AddressTaken<'m>>,
     /// `FunctionsByType`, which allows you to iterate over functions by type
     functions_by_type: SimpleCache<FunctionsByType<'m>>,
+    /// `Vtables`, which allows you to iterate over the functions stored in
+    /// vtables
+    vtables: SimpleCache<Vtables<'m>>,
     /// Map from module name to the `ModuleAnalysis` for that module
     module_analyses: HashMap<&'m str, ModuleAnalysis<'m>>,
 }
@@ -117,12 +217,31 @@ impl<'m> CrossModuleAnalysis<'m> {
             .collect();
         Self {
             modules,
+            call_graph_options: CallGraphOptions::default(),
             call_graph: SimpleCache::new(),
+            address_taken: SimpleCache::new(),
             functions_by_type: SimpleCache::new(),
+            vtables: SimpleCache::new(),
             module_analyses,
         }
     }
 
+    /// Set the options used when computing the `CallGraph`s, both for the
+    /// `Module`(s) as a whole and for each individual `ModuleAnalysis`.
+    pub fn with_call_graph_options(self, call_graph_options: CallGraphOptions) -> Self {
+        Self {
+            call_graph_options,
+            module_analyses: self
+                .module_analyses
+                .into_iter()
+                .map(|(name, analysis)| {
+                    (name, analysis.with_call_graph_options(call_graph_options))
+                })
+                .collect(),
+            ..self
+        }
+    }
+
     /// Iterate over the analyzed `Module`(s).
     pub fn modules<'s>(&'s self) -> impl Iterator<Item = &'m Module> + 's {
         self.modules.iter().copied()
@@ -139,8 +258,26 @@ impl<'m> CrossModuleAnalysis<'m> {
     pub fn call_graph(&self) -> Ref<CallGraph<'m>> {
         self.call_graph.get_or_insert_with(|| {
             let functions_by_type = self.functions_by_type();
+            let vtables = self.vtables();
+            let address_taken = self
+                .call_graph_options
+                .address_taken_only
+                .then(|| self.address_taken());
             debug!("computing multi-module call graph");
-            CallGraph::new(self.modules(), &functions_by_type)
+            CallGraph::new(
+                self.modules(),
+                &functions_by_type,
+                &vtables,
+                address_taken.as_deref(),
+            )
+        })
+    }
+
+    /// Get the `AddressTaken` for the `Module`(s).
+    pub fn address_taken(&self) -> Ref<AddressTaken<'m>> {
+        self.address_taken.get_or_insert_with(|| {
+            debug!("computing multi-module address-taken functions");
+            AddressTaken::new(self.modules())
         })
     }
 
@@ -152,6 +289,14 @@ impl<'m> CrossModuleAnalysis<'m> {
         })
     }
 
+    /// Get the `Vtables` for the `Module`(s).
+    pub fn vtables(&self) -> Ref<Vtables<'m>> {
+        self.vtables.get_or_insert_with(|| {
+            debug!("computing multi-module vtables");
+            Vtables::new(self.modules())
+        })
+    }
+
     /// Get the `ModuleAnalysis` for the module with the given name.
     ///
     /// Panics if no module of that name exists in the `Module`(s) which the
@@ -0,0 +1,462 @@
+use crate::address_taken::AddressTaken;
+use crate::call_graph::{CallGraphOptions, CallOrInvoke, CallResolution};
+use crate::control_dep_graph::ControlDependenceGraph;
+use crate::control_flow_graph::{CFGNode, ControlFlowGraph};
+use crate::dominator_tree::PostDominatorTree;
+use crate::vtables::{self, LocalDefs, Vtables};
+use either::Either;
+use llvm_ir::{Constant, DebugLoc, Function, Instruction, Module, Name, Operand, Terminator};
+use petgraph::prelude::*;
+use petgraph::visit::{Reversed, Walker};
+use std::collections::{HashMap, HashSet};
+
+/// Everything in a `Module` that's needed to build a cross-module call graph,
+/// without borrowing from the `Module`.
+///
+/// Building a [`CallGraph`](struct.CallGraph.html) requires every analyzed
+/// `Module` to be in memory at once. A `ModuleSummary` can instead be computed
+/// for each `Module` separately (and, with the `serde` feature, stored), and
+/// then combined into a [`SummaryCallGraph`](struct.SummaryCallGraph.html)
+/// once every `Module` has been summarized.
+///
+/// Types are represented by their textual LLVM IR, which identifies them
+/// across `Module`s in the same way that `TypeRef` equality does.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModuleSummary {
+    /// The functions defined in the `Module`
+    pub functions: Vec<FunctionSummary>,
+    /// The calls (and invokes) made by functions in the `Module`, excluding
+    /// calls to inline assembly
+    pub calls: Vec<CallSummary>,
+    /// The functions stored in each method slot of the `Module`'s vtables
+    pub vtable_slots: Vec<VtableSlot>,
+    /// The functions whose address is taken within the `Module`, as
+    /// determined by [`AddressTaken`](struct.AddressTaken.html)
+    pub address_taken: Vec<String>,
+}
+
+/// A function defined in a summarized `Module`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionSummary {
+    pub name: String,
+    /// The function's type
+    pub ty: String,
+}
+
+/// A call (or invoke) in a summarized `Module`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CallSummary {
+    /// The name of the calling function
+    pub caller: String,
+    pub callee: CalleeSummary,
+    #[cfg_attr(feature = "serde", serde(with = "serde_debugloc"))]
+    pub debugloc: Option<DebugLoc>,
+    /// Is this an `invoke`, which may unwind to a landing pad, rather than a
+    /// plain `call`?
+    pub is_invoke: bool,
+    /// Under what circumstances the call is made, relative to the calling
+    /// function's entry
+    pub context: CallContext,
+}
+
+/// Under what circumstances a summarized call is made, as determined from the
+/// control dependences of the block containing it.
+///
+/// Variants are ordered from the least to the most certain to be made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CallContext {
+    /// The call is only made while panicking: either while unwinding, or on
+    /// a path that can never return normally.
+    Panic,
+    /// The call is only made under some branch.
+    Conditional,
+    /// The call is made whenever the function returns normally.
+    Unconditional,
+}
+
+/// What a summarized call is known about its callee, before resolving it
+/// against every other `Module`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CalleeSummary {
+    /// A direct call to the named function.
+    Direct(String),
+    /// A call through a function pointer loaded from the given byte offset
+    /// within a vtable, with the given function type.
+    Vtable { offset: u64, ty: String },
+    /// A call through a function pointer with the given function type, loaded
+    /// from the given byte offset of a pointer that may or may not be a
+    /// vtable.
+    MaybeVtable { offset: u64, ty: String },
+    /// A call through any other function pointer with the given function
+    /// type.
+    Indirect { ty: String },
+}
+
+/// A function stored in a method slot of a vtable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VtableSlot {
+    /// The byte offset of the slot within the vtable
+    pub offset: u64,
+    pub function: String,
+    /// The function's type
+    pub ty: String,
+}
+
+impl ModuleSummary {
+    pub fn new(module: &Module) -> Self {
+        let functions = module
+            .functions
+            .iter()
+            .map(|f| FunctionSummary {
+                name: f.name.clone(),
+                ty: module.type_of(f).to_string(),
+            })
+            .collect();
+
+        let mut calls = Vec::new();
+        for f in &module.functions {
+            let defs = LocalDefs::new(f);
+            let contexts = call_contexts(f);
+            for bb in &f.basic_blocks {
+                let context = contexts
+                    .get(&bb.name)
+                    .copied()
+                    .unwrap_or(CallContext::Conditional);
+                let invoke = match &bb.term {
+                    Terminator::Invoke(invoke) => Some(CallOrInvoke::Invoke {
+                        module,
+                        invoke,
+                        resolution: CallResolution::Direct,
+                    }),
+                    _ => None,
+                };
+                let call_or_invokes = bb
+                    .instrs
+                    .iter()
+                    .filter_map(|inst| match inst {
+                        Instruction::Call(call) => Some(CallOrInvoke::Call {
+                            module,
+                            call,
+                            resolution: CallResolution::Direct,
+                        }),
+                        _ => None,
+                    })
+                    .chain(invoke);
+                for call in call_or_invokes {
+                    if let Some(callee) = summarize_callee(module, &defs, &call) {
+                        calls.push(CallSummary {
+                            caller: f.name.clone(),
+                            callee,
+                            debugloc: call.debugloc().cloned(),
+                            is_invoke: call.is_invoke(),
+                            context,
+                        });
+                    }
+                }
+            }
+        }
+
+        let vtable_slots = Vtables::new(std::iter::once(module))
+            .slots()
+            .map(|(offset, function, ty)| VtableSlot {
+                offset,
+                function: function.to_string(),
+                ty: ty.to_string(),
+            })
+            .collect();
+
+        let address_taken = AddressTaken::new(std::iter::once(module))
+            .functions()
+            .map(String::from)
+            .collect();
+
+        Self {
+            functions,
+            calls,
+            vtable_slots,
+            address_taken,
+        }
+    }
+}
+
+/// Determines the context of the calls in each block of the function.
+///
+/// Unwind edges are ignored, so that calls which may unwind don't make
+/// everything after them conditional. If the function can never return
+/// normally, as when it always panics, blocks ending in `unreachable` are
+/// treated as returning instead.
+fn call_contexts(f: &Function) -> HashMap<&Name, CallContext> {
+    if f.basic_blocks.is_empty()
+        || f.basic_blocks
+            .iter()
+            .any(|bb| matches!(bb.term, Terminator::CallBr(_)))
+    {
+        return HashMap::new();
+    }
+
+    let mut cfg = ControlFlowGraph::new(f);
+    cfg.graph.add_node(CFGNode::Return);
+    for bb in &f.basic_blocks {
+        cfg.graph.add_node(CFGNode::Block(&bb.name));
+        if let Terminator::Invoke(invoke) = &bb.term {
+            if invoke.exception_label != invoke.return_label {
+                cfg.graph.remove_edge(
+                    CFGNode::Block(&bb.name),
+                    CFGNode::Block(&invoke.exception_label),
+                );
+            }
+        }
+    }
+
+    let reachable = |cfg: &ControlFlowGraph<'_>| {
+        Dfs::new(&cfg.graph, cfg.entry_node)
+            .iter(&cfg.graph)
+            .any(|node| node == CFGNode::Return)
+    };
+    if !reachable(&cfg) {
+        for bb in &f.basic_blocks {
+            if let Terminator::Unreachable(_) = bb.term {
+                cfg.graph
+                    .add_edge(CFGNode::Block(&bb.name), CFGNode::Return, ());
+            }
+        }
+        if !reachable(&cfg) {
+            // An infinite loop, most likely; we can't say anything useful.
+            return HashMap::new();
+        }
+    }
+
+    let from_entry: HashSet<CFGNode> = Dfs::new(&cfg.graph, cfg.entry_node)
+        .iter(&cfg.graph)
+        .collect();
+    let to_return: HashSet<CFGNode> = Dfs::new(Reversed(&cfg.graph), CFGNode::Return)
+        .iter(Reversed(&cfg.graph))
+        .collect();
+
+    let postdomtree = PostDominatorTree::new(&cfg);
+    let cdg = ControlDependenceGraph::new(&cfg, &postdomtree);
+
+    f.basic_blocks
+        .iter()
+        .map(|bb| {
+            let node = CFGNode::Block(&bb.name);
+            let context = if !from_entry.contains(&node) || !to_return.contains(&node) {
+                CallContext::Panic
+            } else if cdg.get_imm_control_dependencies(&bb.name).next().is_none() {
+                CallContext::Unconditional
+            } else {
+                CallContext::Conditional
+            };
+            (&bb.name, context)
+        })
+        .collect()
+}
+
+fn summarize_callee(
+    module: &Module,
+    defs: &LocalDefs,
+    call: &CallOrInvoke,
+) -> Option<CalleeSummary> {
+    match call.callee() {
+        Either::Right(Operand::ConstantOperand(cref)) => match cref.as_ref() {
+            Constant::GlobalReference {
+                name: Name::Name(name),
+                ..
+            } => Some(CalleeSummary::Direct(name.to_string())),
+            // A call of a function with a numbered name, which doesn't happen
+            // in practice.
+            Constant::GlobalReference { .. } => None,
+            _ => Some(CalleeSummary::Indirect {
+                ty: call.callee_ty().to_string(),
+            }),
+        },
+        Either::Right(callee) => {
+            let ty = call.callee_ty().to_string();
+            Some(match vtables::method_slot(module, defs, callee) {
+                Some(slot) if slot.is_dyn_metadata => CalleeSummary::Vtable {
+                    offset: slot.offset,
+                    ty,
+                },
+                Some(slot) => CalleeSummary::MaybeVtable {
+                    offset: slot.offset,
+                    ty,
+                },
+                None => CalleeSummary::Indirect { ty },
+            })
+        }
+        Either::Left(_) => None, // ignore calls to inline assembly
+    }
+}
+
+/// A cross-module call graph built from [`ModuleSummary`](struct.ModuleSummary.html)s.
+///
+/// Calls are resolved in the same way as in a
+/// [`CallGraph`](struct.CallGraph.html) built from the `Module`s themselves.
+pub struct SummaryCallGraph<'s> {
+    /// the call graph itself. Nodes are function names, and an edge from F to G
+    /// indicates F may call G
+    graph: DiGraphMap<&'s str, SummaryCall<'s>>,
+}
+
+/// An edge in a [`SummaryCallGraph`](struct.SummaryCallGraph.html).
+#[derive(Clone, Copy, Debug)]
+pub struct SummaryCall<'s> {
+    pub call: &'s CallSummary,
+    /// How the target of this call was determined
+    pub resolution: CallResolution,
+}
+
+impl<'s> SummaryCallGraph<'s> {
+    pub fn new(
+        summaries: impl IntoIterator<Item = &'s ModuleSummary>,
+        options: CallGraphOptions,
+    ) -> Self {
+        let summaries: Vec<&'s ModuleSummary> = summaries.into_iter().collect();
+        let address_taken: Option<HashSet<&'s str>> = options.address_taken_only.then(|| {
+            summaries
+                .iter()
+                .copied()
+                .flat_map(|summary| summary.address_taken.iter().map(String::as_str))
+                .collect()
+        });
+
+        let mut functions_by_type: HashMap<&'s str, Vec<&'s str>> = HashMap::new();
+        for function in summaries
+            .iter()
+            .copied()
+            .flat_map(|summary| &summary.functions)
+        {
+            if address_taken
+                .as_ref()
+                .map_or(true, |at| at.contains(function.name.as_str()))
+            {
+                functions_by_type
+                    .entry(&function.ty)
+                    .or_default()
+                    .push(&function.name);
+            }
+        }
+        let functions_with_type =
+            |ty: &str| -> &[&'s str] { functions_by_type.get(ty).map_or(&[], Vec::as_slice) };
+
+        let mut vtable_slots: HashMap<u64, Vec<&'s VtableSlot>> = HashMap::new();
+        for slot in summaries
+            .iter()
+            .copied()
+            .flat_map(|summary| &summary.vtable_slots)
+        {
+            vtable_slots.entry(slot.offset).or_default().push(slot);
+        }
+        let functions_in_slot = |offset: u64, ty: &str| -> Vec<&'s str> {
+            let mut targets: Vec<&'s str> = vtable_slots
+                .get(&offset)
+                .into_iter()
+                .flatten()
+                .filter(|slot| vtables::slot_type_text_matches(&slot.ty, ty))
+                .map(|slot| slot.function.as_str())
+                .collect();
+            targets.sort_unstable();
+            targets.dedup();
+            targets
+        };
+
+        let mut graph: DiGraphMap<&'s str, SummaryCall<'s>> = DiGraphMap::new();
+        for function in summaries
+            .iter()
+            .copied()
+            .flat_map(|summary| &summary.functions)
+        {
+            graph.add_node(&function.name); // just to ensure all functions end up getting nodes in the graph by the end
+        }
+
+        for call in summaries.iter().copied().flat_map(|summary| &summary.calls) {
+            let (targets, resolution): (Vec<&'s str>, _) = match &call.callee {
+                CalleeSummary::Direct(name) => (vec![name.as_str()], CallResolution::Direct),
+                CalleeSummary::Vtable { offset, ty } => {
+                    let targets = functions_in_slot(*offset, ty);
+                    if targets.is_empty() {
+                        (functions_with_type(ty).to_vec(), CallResolution::Indirect)
+                    } else {
+                        (targets, CallResolution::Vtable)
+                    }
+                }
+                CalleeSummary::MaybeVtable { offset, ty } => {
+                    let mut targets = functions_in_slot(*offset, ty);
+                    targets.extend_from_slice(functions_with_type(ty));
+                    targets.sort_unstable();
+                    targets.dedup();
+                    (targets, CallResolution::Indirect)
+                }
+                CalleeSummary::Indirect { ty } => {
+                    (functions_with_type(ty).to_vec(), CallResolution::Indirect)
+                }
+            };
+
+            // Add an edge, unless there's already an edge between the same
+            // functions that was resolved with more certainty, or that's more
+            // certain to be made.
+            for target in targets {
+                let existing = graph.edge_weight(&call.caller, target);
+                if existing.map_or(true, |existing| {
+                    (existing.resolution, existing.call.context) <= (resolution, call.context)
+                }) {
+                    graph.add_edge(&call.caller, target, SummaryCall { call, resolution });
+                }
+            }
+        }
+
+        Self { graph }
+    }
+
+    pub fn inner(&self) -> &DiGraphMap<&'s str, SummaryCall<'s>> {
+        &self.graph
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_debugloc {
+    use llvm_ir::DebugLoc;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Raw {
+        line: u32,
+        col: Option<u32>,
+        filename: String,
+        directory: Option<String>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        debugloc: &Option<DebugLoc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        debugloc
+            .as_ref()
+            .map(|debugloc| Raw {
+                line: debugloc.line,
+                col: debugloc.col,
+                filename: debugloc.filename.clone(),
+                directory: debugloc.directory.clone(),
+            })
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DebugLoc>, D::Error> {
+        Ok(
+            Option::<Raw>::deserialize(deserializer)?.map(|raw| DebugLoc {
+                line: raw.line,
+                col: raw.col,
+                filename: raw.filename,
+                directory: raw.directory,
+            }),
+        )
+    }
+}
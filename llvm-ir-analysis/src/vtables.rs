@@ -0,0 +1,426 @@
+use llvm_ir::function::Parameter;
+#[cfg(feature = "llvm-15-or-greater")]
+use llvm_ir::function::ParameterAttribute;
+use llvm_ir::instruction::GetElementPtr;
+use llvm_ir::types::{FPType, NamedStructDef};
+use llvm_ir::{Constant, Function, Instruction, Module, Name, Operand, Type, TypeRef};
+use std::collections::{HashMap, HashSet};
+
+/// The functions stored in each slot of the Rust vtables in the analyzed
+/// `Module`(s).
+///
+/// rustc emits each vtable as a private constant global named `vtable.N`,
+/// laid out as the drop function, size, and alignment, followed by a function
+/// pointer for each trait method. A `dyn Trait` method call loads the function
+/// pointer from a fixed byte offset within the vtable, so the possible targets
+/// of such a call are exactly the functions stored at that offset in some
+/// vtable.
+///
+/// To construct a `Vtables`, use [`ModuleAnalysis`](struct.ModuleAnalysis.html)
+/// or [`CrossModuleAnalysis`](struct.CrossModuleAnalysis.html).
+pub struct Vtables<'m> {
+    /// Map from byte offset within a vtable to the functions stored at that
+    /// offset, along with their types
+    slots: HashMap<u64, HashSet<(&'m str, TypeRef)>>,
+}
+
+impl<'m> Vtables<'m> {
+    pub(crate) fn new(modules: impl IntoIterator<Item = &'m Module>) -> Self {
+        let mut slots: HashMap<u64, HashSet<(&'m str, TypeRef)>> = HashMap::new();
+        for module in modules {
+            for var in &module.global_vars {
+                let is_vtable =
+                    matches!(&var.name, Name::Name(name) if name.starts_with("vtable."));
+                let Some(init) = var.initializer.as_ref().filter(|_| is_vtable) else {
+                    continue;
+                };
+                let mut entries = Vec::new();
+                flatten_functions(module, init, 0, &mut entries);
+                for (offset, name, ty) in entries {
+                    slots.entry(offset).or_default().insert((name, ty));
+                }
+            }
+        }
+        Self { slots }
+    }
+
+    /// Iterate over all of the functions stored at the given byte offset in
+    /// any vtable in the analyzed `Module`(s) that may be called through a
+    /// function pointer of the given type.
+    ///
+    /// With typed pointers, rustc casts the vtable entry to a type with an
+    /// erased receiver before calling it, so the type is only used to filter
+    /// the functions when the `Module`(s) use opaque pointers.
+    pub fn functions_at_offset<'s>(
+        &'s self,
+        offset: u64,
+        ty: &'s TypeRef,
+    ) -> impl Iterator<Item = &'m str> + 's {
+        self.slots
+            .get(&offset)
+            .into_iter()
+            .flatten()
+            .filter(move |(_, slot_ty)| slot_type_matches(slot_ty, ty))
+            .map(|(name, _)| *name)
+    }
+
+    /// Iterate over every function stored in a method slot of any vtable,
+    /// along with the slot's byte offset and the function's type.
+    pub(crate) fn slots<'s>(&'s self) -> impl Iterator<Item = (u64, &'m str, &'s TypeRef)> + 's {
+        self.slots
+            .iter()
+            .flat_map(|(offset, slot)| slot.iter().map(move |(name, ty)| (*offset, *name, ty)))
+    }
+}
+
+#[cfg(feature = "llvm-15-or-greater")]
+fn slot_type_matches(slot_ty: &TypeRef, ty: &TypeRef) -> bool {
+    slot_ty == ty
+}
+
+#[cfg(feature = "llvm-14-or-lower")]
+fn slot_type_matches(_slot_ty: &TypeRef, _ty: &TypeRef) -> bool {
+    true
+}
+
+/// Like `slot_type_matches`, but for types represented by their textual IR.
+#[cfg(feature = "llvm-15-or-greater")]
+pub(crate) fn slot_type_text_matches(slot_ty: &str, ty: &str) -> bool {
+    slot_ty == ty
+}
+
+#[cfg(feature = "llvm-14-or-lower")]
+pub(crate) fn slot_type_text_matches(_slot_ty: &str, _ty: &str) -> bool {
+    true
+}
+
+/// A function pointer loaded from a method slot of what may be a vtable.
+pub(crate) struct MethodSlot {
+    /// The byte offset of the slot within the vtable
+    pub offset: u64,
+    /// Is the pointer that the slot was loaded from known to be the metadata
+    /// half of a `dyn` fat pointer? If not, it may just as well be a struct
+    /// with a function pointer at the same offset.
+    pub is_dyn_metadata: bool,
+}
+
+/// If the given operand (the callee of an indirect call) was loaded from what
+/// may be a method slot of a vtable, returns that slot.
+///
+/// The drop, size, and alignment slots at the start of the vtable are never
+/// reported, since a function pointer loaded from the start of a struct can't
+/// be distinguished from one loaded from a vtable.
+pub(crate) fn method_slot(
+    module: &Module,
+    defs: &LocalDefs,
+    callee: &Operand,
+) -> Option<MethodSlot> {
+    let Operand::LocalOperand { name, .. } = callee else {
+        return None;
+    };
+    let Some(Instruction::Load(load)) = defs.get(name) else {
+        return None;
+    };
+
+    let (base, offset) = address_offset(module, defs, &load.address)?;
+    if offset >= 3 * pointer_size(module) {
+        Some(MethodSlot {
+            offset,
+            is_dyn_metadata: is_dyn_metadata(module, defs, base, offset),
+        })
+    } else {
+        None
+    }
+}
+
+/// Is the given pointer, which a function pointer is loaded from at the given
+/// offset, the metadata half of a `dyn` fat pointer?
+///
+/// With typed pointers, rustc always gives vtable pointers the type
+/// `[3 x usize]*`, and nothing else loads a function pointer from beyond the
+/// end of such an array.
+#[cfg(feature = "llvm-14-or-lower")]
+fn is_dyn_metadata(module: &Module, _defs: &LocalDefs, pointer: &Operand, _offset: u64) -> bool {
+    let ty = module.type_of(pointer);
+    let Type::PointerType { pointee_type, .. } = ty.as_ref() else {
+        return false;
+    };
+    match pointee_type.as_ref() {
+        Type::ArrayType {
+            element_type,
+            num_elements: 3,
+        } => matches!(
+            element_type.as_ref(),
+            Type::IntegerType { bits } if u64::from(*bits) == 8 * pointer_size(module)
+        ),
+        _ => false,
+    }
+}
+
+/// With opaque pointers, the `!invariant.load` metadata on the load is the
+/// surest sign of a vtable, but `llvm-ir` doesn't expose instruction metadata.
+/// A fat pointer that was passed as an argument can still be recognised from
+/// its parameter attributes, though: rustc marks the metadata half as
+/// `dereferenceable` to exactly the size of the vtable, while the data half
+/// points to something unsized, so isn't `dereferenceable` at all. A pointer
+/// to an ordinary struct is never preceded by such a data pointer.
+///
+/// Vtable pointers that are loaded from memory still can't be told apart from
+/// any other pointer.
+#[cfg(feature = "llvm-15-or-greater")]
+fn is_dyn_metadata(module: &Module, defs: &LocalDefs, pointer: &Operand, offset: u64) -> bool {
+    let Operand::LocalOperand { name, .. } = pointer else {
+        return false;
+    };
+    let Some(idx) = defs.params.iter().position(|param| &param.name == name) else {
+        return false;
+    };
+    let Some(data) = idx.checked_sub(1).map(|idx| &defs.params[idx]) else {
+        return false;
+    };
+    let metadata = &defs.params[idx];
+
+    let ptr_size = pointer_size(module);
+    is_pointer(data)
+        && dereferenceable(data).is_none()
+        && is_pointer(metadata)
+        && dereferenceable(metadata)
+            .is_some_and(|size| size % ptr_size == 0 && offset + ptr_size <= size)
+}
+
+#[cfg(feature = "llvm-15-or-greater")]
+fn is_pointer(param: &Parameter) -> bool {
+    matches!(param.ty.as_ref(), Type::PointerType { .. })
+}
+
+/// The number of bytes the given parameter is known to be dereferenceable
+/// for, if any.
+#[cfg(feature = "llvm-15-or-greater")]
+fn dereferenceable(param: &Parameter) -> Option<u64> {
+    param.attributes.iter().find_map(|attr| match attr {
+        ParameterAttribute::Dereferenceable(size)
+        | ParameterAttribute::DereferenceableOrNull(size) => Some(*size),
+        _ => None,
+    })
+}
+
+/// Map from local names to the instructions that define them within a single
+/// function, along with the function's parameters.
+pub(crate) struct LocalDefs<'m> {
+    map: HashMap<&'m Name, &'m Instruction>,
+    #[cfg_attr(feature = "llvm-14-or-lower", allow(dead_code))]
+    params: &'m [Parameter],
+}
+
+impl<'m> LocalDefs<'m> {
+    pub(crate) fn new(f: &'m Function) -> Self {
+        let map = f
+            .basic_blocks
+            .iter()
+            .flat_map(|bb| bb.instrs.iter())
+            .filter_map(|inst| inst.try_get_result().map(|name| (name, inst)))
+            .collect();
+        Self {
+            map,
+            params: &f.parameters,
+        }
+    }
+
+    fn get(&self, name: &Name) -> Option<&'m Instruction> {
+        self.map.get(name).copied()
+    }
+}
+
+/// The pointer that the given address was derived from via constant
+/// `getelementptr`s and casts, along with the address's byte offset from it.
+fn address_offset<'a>(
+    module: &Module,
+    defs: &LocalDefs<'a>,
+    address: &'a Operand,
+) -> Option<(&'a Operand, u64)> {
+    let Operand::LocalOperand { name, .. } = address else {
+        return Some((address, 0));
+    };
+    match defs.get(name) {
+        Some(Instruction::GetElementPtr(gep)) => {
+            let (base, offset) = address_offset(module, defs, &gep.address)?;
+            Some((base, offset.wrapping_add(gep_offset(module, gep)?)))
+        }
+        Some(Instruction::BitCast(bc)) => address_offset(module, defs, &bc.operand),
+        Some(Instruction::AddrSpaceCast(asc)) => address_offset(module, defs, &asc.operand),
+        _ => Some((address, 0)),
+    }
+}
+
+fn gep_offset(module: &Module, gep: &GetElementPtr) -> Option<u64> {
+    let mut indices = gep.indices.iter().map(|index| match index {
+        Operand::ConstantOperand(cref) => match cref.as_ref() {
+            Constant::Int { value, .. } => Some(*value),
+            _ => None,
+        },
+        _ => None,
+    });
+
+    #[cfg(feature = "llvm-14-or-greater")]
+    let mut ty = gep.source_element_type.clone();
+    #[cfg(feature = "llvm-13-or-lower")]
+    let mut ty = match module.type_of(&gep.address).as_ref() {
+        Type::PointerType { pointee_type, .. } => pointee_type.clone(),
+        _ => return None,
+    };
+
+    let mut offset = indices.next()??.wrapping_mul(size_of(module, &ty)?);
+    for index in indices {
+        let index = index?;
+        let (field_offset, field_ty) = match resolve(module, &ty)? {
+            Type::StructType {
+                element_types,
+                is_packed,
+            } => {
+                let fields = struct_field_offsets(module, element_types, *is_packed)?;
+                let field = usize::try_from(index).ok()?;
+                (
+                    fields.get(field).copied()?,
+                    element_types.get(field)?.clone(),
+                )
+            }
+            Type::ArrayType { element_type, .. } | Type::VectorType { element_type, .. } => (
+                index.wrapping_mul(size_of(module, element_type)?),
+                element_type.clone(),
+            ),
+            _ => return None,
+        };
+        offset = offset.wrapping_add(field_offset);
+        ty = field_ty;
+    }
+    Some(offset)
+}
+
+/// Collects the functions referenced by the given constant, along with the
+/// byte offset at which each is stored.
+fn flatten_functions<'m>(
+    module: &Module,
+    constant: &'m Constant,
+    offset: u64,
+    out: &mut Vec<(u64, &'m str, TypeRef)>,
+) {
+    match constant {
+        Constant::GlobalReference {
+            name: Name::Name(name),
+            ty,
+        } if matches!(ty.as_ref(), Type::FuncType { .. }) => out.push((offset, name, ty.clone())),
+        Constant::Struct {
+            values, is_packed, ..
+        } => {
+            let types: Vec<TypeRef> = values.iter().map(|value| module.type_of(value)).collect();
+            if let Some(fields) = struct_field_offsets(module, &types, *is_packed) {
+                for (value, field_offset) in values.iter().zip(fields) {
+                    flatten_functions(module, value, offset + field_offset, out);
+                }
+            }
+        }
+        Constant::Array {
+            element_type,
+            elements,
+        } => {
+            if let Some(size) = size_of(module, element_type) {
+                for (i, element) in elements.iter().enumerate() {
+                    flatten_functions(module, element, offset + i as u64 * size, out);
+                }
+            }
+        }
+        Constant::BitCast(cast) => flatten_functions(module, &cast.operand, offset, out),
+        Constant::AddrSpaceCast(cast) => flatten_functions(module, &cast.operand, offset, out),
+        _ => {}
+    }
+}
+
+fn resolve<'t>(module: &'t Module, ty: &'t TypeRef) -> Option<&'t Type> {
+    match ty.as_ref() {
+        Type::NamedStructType { name } => match module.types.named_struct_def(name)? {
+            NamedStructDef::Defined(ty) => Some(ty.as_ref()),
+            NamedStructDef::Opaque => None,
+        },
+        ty => Some(ty),
+    }
+}
+
+fn pointer_size(module: &Module) -> u64 {
+    u64::from(module.data_layout.alignments.ptr_alignment(0).size / 8)
+}
+
+/// Allocation size of the given type in bytes, or `None` if it can't be
+/// determined.
+fn size_of(module: &Module, ty: &TypeRef) -> Option<u64> {
+    match resolve(module, ty)? {
+        Type::IntegerType { bits } => {
+            let align = align_of(module, ty)?;
+            Some(u64::from(*bits).div_ceil(8).next_multiple_of(align))
+        }
+        Type::PointerType { .. } => Some(pointer_size(module)),
+        Type::FPType(fpt) => Some(match fpt {
+            FPType::Half => 2,
+            #[cfg(feature = "llvm-11-or-greater")]
+            FPType::BFloat => 2,
+            FPType::Single => 4,
+            FPType::Double => 8,
+            FPType::FP128 | FPType::X86_FP80 | FPType::PPC_FP128 => 16,
+        }),
+        Type::ArrayType {
+            element_type,
+            num_elements,
+        } => Some(size_of(module, element_type)? * *num_elements as u64),
+        Type::VectorType {
+            element_type,
+            num_elements,
+            ..
+        } => Some(size_of(module, element_type)? * *num_elements as u64),
+        Type::StructType {
+            element_types,
+            is_packed,
+        } => {
+            let fields = struct_field_offsets(module, element_types, *is_packed)?;
+            let end = match (fields.last(), element_types.last()) {
+                (Some(offset), Some(ty)) => offset + size_of(module, ty)?,
+                _ => 0,
+            };
+            Some(end.next_multiple_of(align_of(module, ty)?))
+        }
+        _ => None,
+    }
+}
+
+/// ABI alignment of the given type in bytes, or `None` if it can't be
+/// determined.
+fn align_of(module: &Module, ty: &TypeRef) -> Option<u64> {
+    match resolve(module, ty)? {
+        Type::IntegerType { bits } => {
+            let align = module.data_layout.alignments.int_alignment(*bits).abi / 8;
+            Some(u64::from(align).max(1))
+        }
+        Type::PointerType { .. } => Some(pointer_size(module)),
+        Type::FPType(_) | Type::VectorType { .. } => size_of(module, ty),
+        Type::ArrayType { element_type, .. } => align_of(module, element_type),
+        Type::StructType {
+            is_packed: true, ..
+        } => Some(1),
+        Type::StructType { element_types, .. } => element_types
+            .iter()
+            .try_fold(1, |align, ty| Some(u64::max(align, align_of(module, ty)?))),
+        _ => None,
+    }
+}
+
+/// Byte offset of each field within a struct with the given field types.
+fn struct_field_offsets(module: &Module, types: &[TypeRef], is_packed: bool) -> Option<Vec<u64>> {
+    let mut offsets = Vec::with_capacity(types.len());
+    let mut offset = 0;
+    for ty in types {
+        if !is_packed {
+            offset = u64::next_multiple_of(offset, align_of(module, ty)?);
+        }
+        offsets.push(offset);
+        offset += size_of(module, ty)?;
+    }
+    Some(offsets)
+}
@@ -0,0 +1,63 @@
+use itertools::Itertools;
+use llvm_ir::Module;
+use llvm_ir_analysis::*;
+
+fn init_logging() {
+    // capture log messages with test harness
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+/// vtable.ll is hand-written to mirror the IR that rustc emits for trait
+/// objects; see the comment at the top of that file
+const VTABLE_BC_PATH: &'static str = "tests/bcfiles/vtable.bc";
+
+#[test]
+fn address_taken() {
+    init_logging();
+    let module = Module::from_bc_path(VTABLE_BC_PATH)
+        .unwrap_or_else(|e| panic!("Failed to parse module: {}", e));
+    let analysis = ModuleAnalysis::new(&module);
+    let address_taken = analysis.address_taken();
+
+    let functions: Vec<&str> = address_taken.functions().sorted().collect();
+    assert_eq!(
+        functions,
+        vec![
+            "cat_drop",
+            "cat_shout",
+            "cat_speak",
+            "dog_shout",
+            "dog_speak",
+            "unrelated",
+        ]
+    );
+    assert!(!address_taken.contains("never_taken"));
+    assert!(!address_taken.contains("calls_speak"));
+}
+
+#[test]
+fn address_taken_call_graph() {
+    init_logging();
+    let module = Module::from_bc_path(VTABLE_BC_PATH)
+        .unwrap_or_else(|e| panic!("Failed to parse module: {}", e));
+
+    // by default, any function with the right type may be called
+    let analysis = ModuleAnalysis::new(&module);
+    let callgraph = analysis.call_graph();
+    let callees: Vec<&str> = callgraph.callees("calls_fptr").sorted().collect();
+    assert!(callees.contains(&"never_taken"));
+    assert!(callees.contains(&"unrelated"));
+
+    // but only functions whose address is taken may be called if requested
+    let analysis = ModuleAnalysis::new(&module).with_call_graph_options(CallGraphOptions {
+        address_taken_only: true,
+    });
+    let callgraph = analysis.call_graph();
+    let callees: Vec<&str> = callgraph.callees("calls_fptr").sorted().collect();
+    assert!(!callees.contains(&"never_taken"));
+    assert!(callees.contains(&"unrelated"));
+
+    // calls through vtables are unaffected
+    let callees: Vec<&str> = callgraph.callees("calls_speak").sorted().collect();
+    assert_eq!(callees, vec!["cat_speak", "dog_speak"]);
+}
@@ -0,0 +1,118 @@
+; Hand-written to mirror the IR that rustc emits for trait objects, using typed
+; pointers so that it can be read by every supported LLVM version. Roughly:
+;
+;     pub trait Speak {
+;         fn speak(&self) -> i32;
+;         fn shout(&self, times: i32) -> i32;
+;     }
+;
+;     impl Speak for Dog { ... }
+;     impl Speak for Cat { ... } // Cat also implements Drop
+;
+;     pub fn calls_speak(speaker: &dyn Speak) -> i32 { speaker.speak() }
+;     pub fn calls_shout(speaker: &dyn Speak) -> i32 { speaker.shout(3) }
+;     pub fn calls_fptr(f: fn(&i32) -> i32) -> i32 { f(&4) }
+;     pub fn calls_callback(callbacks: &Callbacks) -> i32 { (callbacks.on_four)(&4) }
+;
+; `Callbacks` is an ordinary struct, but its function pointer is at the same
+; offset as the `speak` methods in the vtables.
+; `unrelated` has the same type as the `speak` methods, but is never stored in a
+; vtable; `not_a_vtable` has the same layout as a vtable, but isn't one.
+; `never_taken` also has the same type, but its address is never taken.
+
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-unknown-linux-gnu"
+
+%Dog = type {}
+%Cat = type { i32 }
+%Callbacks = type { i64, i64, i64, i32 (i32*)* }
+
+@vtable.0 = private unnamed_addr constant <{ i8*, [16 x i8], i8*, i8* }> <{ i8* null, [16 x i8] c"\00\00\00\00\00\00\00\00\01\00\00\00\00\00\00\00", i8* bitcast (i32 (%Dog*)* @dog_speak to i8*), i8* bitcast (i32 (%Dog*, i32)* @dog_shout to i8*) }>, align 8
+@vtable.1 = private unnamed_addr constant <{ i8*, [16 x i8], i8*, i8* }> <{ i8* bitcast (void (%Cat*)* @cat_drop to i8*), [16 x i8] c"\04\00\00\00\00\00\00\00\04\00\00\00\00\00\00\00", i8* bitcast (i32 (%Cat*)* @cat_speak to i8*), i8* bitcast (i32 (%Cat*, i32)* @cat_shout to i8*) }>, align 8
+@not_a_vtable = private unnamed_addr constant <{ i8*, [16 x i8], i8* }> <{ i8* null, [16 x i8] zeroinitializer, i8* bitcast (i32 (i32*)* @unrelated to i8*) }>, align 8
+@cat = private unnamed_addr constant %Cat { i32 2 }, align 4
+@four = private unnamed_addr constant i32 4, align 4
+
+define i32 @dog_speak(%Dog* %self) {
+start:
+  ret i32 1
+}
+
+define i32 @dog_shout(%Dog* %self, i32 %times) {
+start:
+  ret i32 %times
+}
+
+define void @cat_drop(%Cat* %self) {
+start:
+  ret void
+}
+
+define i32 @cat_speak(%Cat* %self) {
+start:
+  %0 = getelementptr inbounds %Cat, %Cat* %self, i32 0, i32 0
+  %1 = load i32, i32* %0, align 4
+  ret i32 %1
+}
+
+define i32 @cat_shout(%Cat* %self, i32 %times) {
+start:
+  %0 = getelementptr inbounds %Cat, %Cat* %self, i32 0, i32 0
+  %1 = load i32, i32* %0, align 4
+  %2 = mul i32 %1, %times
+  ret i32 %2
+}
+
+define i32 @unrelated(i32* %x) {
+start:
+  %0 = load i32, i32* %x, align 4
+  ret i32 %0
+}
+
+define i32 @never_taken(i32* %x) {
+start:
+  ret i32 0
+}
+
+define i32 @calls_speak({}* %speaker.0, [3 x i64]* %speaker.1) {
+start:
+  %0 = bitcast [3 x i64]* %speaker.1 to i32 ({}*)**
+  %1 = getelementptr inbounds i32 ({}*)*, i32 ({}*)** %0, i64 3
+  %2 = load i32 ({}*)*, i32 ({}*)** %1, align 8
+  %3 = call i32 %2({}* %speaker.0)
+  ret i32 %3
+}
+
+define i32 @calls_shout({}* %speaker.0, [3 x i64]* %speaker.1) {
+start:
+  %0 = bitcast [3 x i64]* %speaker.1 to i8*
+  %1 = getelementptr inbounds i8, i8* %0, i64 32
+  %2 = bitcast i8* %1 to i32 ({}*, i32)**
+  %3 = load i32 ({}*, i32)*, i32 ({}*, i32)** %2, align 8
+  %4 = call i32 %3({}* %speaker.0, i32 3)
+  ret i32 %4
+}
+
+define i32 @calls_fptr(i32 (i32*)* %f) {
+start:
+  %0 = call i32 %f(i32* @four)
+  ret i32 %0
+}
+
+define i32 @calls_callback(%Callbacks* %callbacks) {
+start:
+  %0 = getelementptr inbounds %Callbacks, %Callbacks* %callbacks, i32 0, i32 3
+  %1 = load i32 (i32*)*, i32 (i32*)** %0, align 8
+  %2 = call i32 %1(i32* @four)
+  ret i32 %2
+}
+
+define i32 @make_speakers() {
+start:
+  %0 = call i32 @calls_speak({}* bitcast (%Dog* undef to {}*), [3 x i64]* bitcast (<{ i8*, [16 x i8], i8*, i8* }>* @vtable.0 to [3 x i64]*))
+  %1 = call i32 @calls_shout({}* bitcast (%Cat* @cat to {}*), [3 x i64]* bitcast (<{ i8*, [16 x i8], i8*, i8* }>* @vtable.1 to [3 x i64]*))
+  %2 = call i32 @calls_fptr(i32 (i32*)* @unrelated)
+  %3 = add i32 %0, %1
+  %4 = add i32 %3, %2
+  ret i32 %4
+}
@@ -0,0 +1,153 @@
+; Emitted by rustc 1.95 with opaque pointers, using
+; `rustc --crate-type=lib -C opt-level=1 -C panic=abort --emit=llvm-ir`, then
+; rewritten so that LLVM 14 can assemble it: `captures(none)` became
+; `nocapture`, `memory(...)` became the equivalent older attributes, `nuw` was
+; dropped from `getelementptr`, `llvm.lifetime.*` regained its size argument,
+; and the PIC level flag uses the `max` behaviour. The source is:
+;
+;     pub trait Speak {
+;         fn speak(&self) -> i32;
+;         fn shout(&self, times: i32) -> i32;
+;     }
+;
+;     pub struct Dog;
+;     pub struct Cat(pub i32);
+;
+;     impl Speak for Dog { ... } // exported as dog_speak and dog_shout
+;     impl Speak for Cat { ... } // exported as cat_speak and cat_shout
+;
+;     #[repr(C)]
+;     pub struct Callbacks {
+;         pub a: usize,
+;         pub b: usize,
+;         pub c: usize,
+;         pub on_four: fn(&i32) -> i32,
+;     }
+;
+;     #[no_mangle]
+;     pub fn calls_speak(speaker: &dyn Speak) -> i32 { speaker.speak() }
+;     #[no_mangle]
+;     pub fn calls_shout(speaker: &dyn Speak) -> i32 { speaker.shout(3) }
+;     #[no_mangle]
+;     pub fn calls_callback(callbacks: &Callbacks) -> i32 { (callbacks.on_four)(&4) }
+;     #[no_mangle]
+;     pub fn unrelated(x: &i32) -> i32 { *x }
+;     #[no_mangle]
+;     pub fn make_speakers() -> i32 { ... }
+;
+; As in vtable.ll, `Callbacks` is an ordinary struct whose function pointer is
+; at the same offset as the `speak` methods in the vtables.
+
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-unknown-linux-gnu"
+
+@alloc_7bf4d83167a444e3cf51563928dad022 = private unnamed_addr constant [4 x i8] c"\04\00\00\00", align 4
+@vtable.0 = private unnamed_addr constant <{ [24 x i8], ptr, ptr }> <{ [24 x i8] c"\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\01\00\00\00\00\00\00\00", ptr @dog_speak, ptr @dog_shout }>, align 8
+@alloc_2e38410fced2c310c68bdf2d45d0c3bd = private unnamed_addr constant [4 x i8] c"\02\00\00\00", align 4
+@vtable.1 = private unnamed_addr constant <{ [24 x i8], ptr, ptr }> <{ [24 x i8] c"\00\00\00\00\00\00\00\00\04\00\00\00\00\00\00\00\04\00\00\00\00\00\00\00", ptr @cat_speak, ptr @cat_shout }>, align 8
+
+; Function Attrs: noinline nounwind nonlazybind uwtable
+define noundef i32 @calls_callback(ptr noalias noundef readonly align 8 nocapture dereferenceable(32) %callbacks) unnamed_addr #0 {
+start:
+  %0 = getelementptr inbounds i8, ptr %callbacks, i64 24
+  %_2 = load ptr, ptr %0, align 8, !nonnull !3, !noundef !3
+  %_0 = tail call noundef i32 %_2(ptr noalias noundef readonly align 4 dereferenceable(4) @alloc_7bf4d83167a444e3cf51563928dad022) #7
+  ret i32 %_0
+}
+
+; Function Attrs: noinline nounwind nonlazybind uwtable
+define noundef i32 @calls_shout(ptr noundef nonnull align 1 %speaker.0, ptr noalias noundef readonly align 8 nocapture dereferenceable(40) %speaker.1) unnamed_addr #0 {
+start:
+  %0 = getelementptr inbounds i8, ptr %speaker.1, i64 32
+  %1 = load ptr, ptr %0, align 8, !invariant.load !3, !nonnull !3
+  %_0 = tail call noundef i32 %1(ptr noundef nonnull align 1 %speaker.0, i32 noundef 3) #8
+  ret i32 %_0
+}
+
+; Function Attrs: noinline nounwind nonlazybind uwtable
+define noundef i32 @calls_speak(ptr noundef nonnull align 1 %speaker.0, ptr noalias noundef readonly align 8 nocapture dereferenceable(40) %speaker.1) unnamed_addr #0 {
+start:
+  %0 = getelementptr inbounds i8, ptr %speaker.1, i64 24
+  %1 = load ptr, ptr %0, align 8, !invariant.load !3, !nonnull !3
+  %_0 = tail call noundef i32 %1(ptr noundef nonnull align 1 %speaker.0) #8
+  ret i32 %_0
+}
+
+; Function Attrs: mustprogress nofree norecurse nosync nounwind nonlazybind willreturn argmemonly readonly uwtable
+define noundef i32 @cat_shout(ptr noalias noundef readonly align 4 nocapture dereferenceable(4) %self, i32 noundef %times) unnamed_addr #1 {
+start:
+  %_3 = load i32, ptr %self, align 4, !noundef !3
+  %_0 = mul i32 %_3, %times
+  ret i32 %_0
+}
+
+; Function Attrs: mustprogress nofree norecurse nosync nounwind nonlazybind willreturn argmemonly readonly uwtable
+define noundef i32 @cat_speak(ptr noalias noundef readonly align 4 nocapture dereferenceable(4) %self) unnamed_addr #1 {
+start:
+  %_0 = load i32, ptr %self, align 4, !noundef !3
+  ret i32 %_0
+}
+
+; Function Attrs: mustprogress nofree norecurse nosync nounwind nonlazybind willreturn readnone uwtable
+define noundef i32 @dog_shout(ptr noalias nonnull readonly align 1 nocapture %self, i32 noundef returned %times) unnamed_addr #2 {
+start:
+  ret i32 %times
+}
+
+; Function Attrs: mustprogress nofree norecurse nosync nounwind nonlazybind willreturn readnone uwtable
+define noundef i32 @dog_speak(ptr noalias nonnull readonly align 1 nocapture %self) unnamed_addr #2 {
+start:
+  ret i32 1
+}
+
+; Function Attrs: nounwind nonlazybind uwtable
+define noundef i32 @make_speakers() unnamed_addr #3 {
+start:
+  %callbacks = alloca [32 x i8], align 8
+  call void @llvm.lifetime.start.p0(i64 32, ptr nonnull %callbacks)
+  %0 = getelementptr inbounds i8, ptr %callbacks, i64 24
+  call void @llvm.memset.p0.i64(ptr noundef nonnull align 8 dereferenceable(24) %callbacks, i8 0, i64 24, i1 false)
+  store ptr @unrelated, ptr %0, align 8
+  %_4 = tail call noundef i32 @calls_speak(ptr noundef nonnull align 1 inttoptr (i64 1 to ptr), ptr noalias noundef readonly align 8 dereferenceable(40) @vtable.0) #9
+  %_6 = tail call noundef i32 @calls_shout(ptr noundef nonnull align 1 @alloc_2e38410fced2c310c68bdf2d45d0c3bd, ptr noalias noundef readonly align 8 dereferenceable(40) @vtable.1) #9
+  %_3 = add i32 %_6, %_4
+  %_8 = call noundef i32 @calls_callback(ptr noalias noundef readonly align 8 dereferenceable(32) %callbacks) #9
+  %_0 = add i32 %_3, %_8
+  call void @llvm.lifetime.end.p0(i64 32, ptr nonnull %callbacks)
+  ret i32 %_0
+}
+
+; Function Attrs: mustprogress nofree noinline norecurse nosync nounwind nonlazybind willreturn argmemonly readonly uwtable
+define noundef i32 @unrelated(ptr noalias noundef readonly align 4 nocapture dereferenceable(4) %x) unnamed_addr #4 {
+start:
+  %_0 = load i32, ptr %x, align 4, !noundef !3
+  ret i32 %_0
+}
+
+; Function Attrs: mustprogress nocallback nofree nosync nounwind willreturn argmemonly
+declare void @llvm.lifetime.start.p0(i64 immarg, ptr nocapture) #5
+
+; Function Attrs: mustprogress nocallback nofree nosync nounwind willreturn argmemonly
+declare void @llvm.lifetime.end.p0(i64 immarg, ptr nocapture) #5
+
+; Function Attrs: nocallback nofree nounwind willreturn argmemonly writeonly
+declare void @llvm.memset.p0.i64(ptr writeonly nocapture, i8, i64, i1 immarg) #6
+
+attributes #0 = { noinline nounwind nonlazybind uwtable "probe-stack"="inline-asm" "target-cpu"="x86-64" }
+attributes #1 = { mustprogress nofree norecurse nosync nounwind nonlazybind willreturn argmemonly readonly uwtable "probe-stack"="inline-asm" "target-cpu"="x86-64" }
+attributes #2 = { mustprogress nofree norecurse nosync nounwind nonlazybind willreturn readnone uwtable "probe-stack"="inline-asm" "target-cpu"="x86-64" }
+attributes #3 = { nounwind nonlazybind uwtable "probe-stack"="inline-asm" "target-cpu"="x86-64" }
+attributes #4 = { mustprogress nofree noinline norecurse nosync nounwind nonlazybind willreturn argmemonly readonly uwtable "probe-stack"="inline-asm" "target-cpu"="x86-64" }
+attributes #5 = { mustprogress nocallback nofree nosync nounwind willreturn argmemonly }
+attributes #6 = { nocallback nofree nounwind willreturn argmemonly writeonly }
+attributes #7 = { nounwind }
+attributes #8 = { inlinehint nounwind }
+attributes #9 = { noinline nounwind }
+
+!llvm.module.flags = !{!0, !1}
+!llvm.ident = !{!2}
+
+!0 = !{i32 7, !"PIC Level", i32 2}
+!1 = !{i32 2, !"RtLibUseGOT", i32 1}
+!2 = !{!"rustc version 1.95.0 (59807616e 2026-04-14)"}
+!3 = !{}
@@ -0,0 +1,178 @@
+use itertools::Itertools;
+use llvm_ir::Module;
+use llvm_ir_analysis::*;
+
+fn init_logging() {
+    // capture log messages with test harness
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+const CALL_BC_PATH: &'static str = "tests/bcfiles/call.bc";
+const FUNCTIONPTR_BC_PATH: &'static str = "tests/bcfiles/functionptr.bc";
+const CROSSMOD_BC_PATH: &'static str = "tests/bcfiles/crossmod.bc";
+const VTABLE_BC_PATH: &'static str = "tests/bcfiles/vtable.bc";
+const VTABLE_OPAQUE_BC_PATH: &'static str = "tests/bcfiles/vtable_opaque.bc";
+const PANIC_BC_PATH: &'static str = "tests/bcfiles/panic.bc";
+
+fn load(paths: &[&str]) -> Vec<Module> {
+    paths
+        .iter()
+        .map(|path| {
+            Module::from_bc_path(path).unwrap_or_else(|e| panic!("Failed to parse module: {}", e))
+        })
+        .collect()
+}
+
+/// The call graph built from the summaries of the given modules should be
+/// exactly the call graph built from the modules themselves.
+fn assert_same_call_graph(paths: &[&str], options: CallGraphOptions) {
+    let modules = load(paths);
+
+    let analysis = CrossModuleAnalysis::new(&modules).with_call_graph_options(options);
+    let callgraph = analysis.call_graph();
+    let expected: Vec<(&str, &str, CallResolution)> = callgraph
+        .inner()
+        .all_edges()
+        .map(|(caller, callee, call)| (caller, callee, call.resolution()))
+        .sorted()
+        .collect();
+
+    let summaries: Vec<ModuleSummary> = modules.iter().map(ModuleSummary::new).collect();
+    let summary_callgraph = SummaryCallGraph::new(&summaries, options);
+    let actual: Vec<(&str, &str, CallResolution)> = summary_callgraph
+        .inner()
+        .all_edges()
+        .map(|(caller, callee, call)| (caller, callee, call.resolution))
+        .sorted()
+        .collect();
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn summary_call_graph() {
+    init_logging();
+    for options in [
+        CallGraphOptions::default(),
+        CallGraphOptions {
+            address_taken_only: true,
+        },
+    ] {
+        assert_same_call_graph(&[CALL_BC_PATH], options);
+        assert_same_call_graph(&[FUNCTIONPTR_BC_PATH], options);
+        assert_same_call_graph(&[VTABLE_BC_PATH], options);
+        assert_same_call_graph(&[CALL_BC_PATH, CROSSMOD_BC_PATH], options);
+        // opaque pointers can only be read with LLVM 15 or later
+        if cfg!(feature = "llvm-15-or-greater") {
+            assert_same_call_graph(&[VTABLE_OPAQUE_BC_PATH], options);
+        }
+    }
+}
+
+#[test]
+fn summary_calls() {
+    init_logging();
+    let modules = load(&[VTABLE_BC_PATH]);
+    let summary = ModuleSummary::new(&modules[0]);
+
+    let callees: Vec<&CalleeSummary> = summary
+        .calls
+        .iter()
+        .filter(|call| call.caller == "calls_speak")
+        .map(|call| &call.callee)
+        .collect();
+    if cfg!(feature = "llvm-14-or-lower") {
+        assert!(matches!(
+            callees.as_slice(),
+            [CalleeSummary::Vtable { offset: 24, .. }]
+        ));
+    } else {
+        assert!(matches!(
+            callees.as_slice(),
+            [CalleeSummary::MaybeVtable { offset: 24, .. }]
+        ));
+    }
+
+    // a function pointer at the same offset of an ordinary struct isn't
+    // mistaken for a vtable slot
+    let callees: Vec<&CalleeSummary> = summary
+        .calls
+        .iter()
+        .filter(|call| call.caller == "calls_callback")
+        .map(|call| &call.callee)
+        .collect();
+    assert!(matches!(
+        callees.as_slice(),
+        [CalleeSummary::MaybeVtable { offset: 24, .. }]
+    ));
+
+    let slots: Vec<(u64, &str)> = summary
+        .vtable_slots
+        .iter()
+        .map(|slot| (slot.offset, slot.function.as_str()))
+        .filter(|(offset, _)| *offset == 24)
+        .sorted()
+        .collect();
+    assert_eq!(slots, vec![(24, "cat_speak"), (24, "dog_speak")]);
+}
+
+#[test]
+fn summary_call_contexts() {
+    init_logging();
+    let modules = load(&[PANIC_BC_PATH, CALL_BC_PATH]);
+    let summaries: Vec<ModuleSummary> = modules.iter().map(ModuleSummary::new).collect();
+    let contexts = |caller: &str| -> Vec<(&str, CallContext)> {
+        summaries
+            .iter()
+            .flat_map(|summary| &summary.calls)
+            .filter(|call| call.caller == caller)
+            .filter_map(|call| match &call.callee {
+                CalleeSummary::Direct(callee) => Some((callee.as_str(), call.context)),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // `may_panic` only calls `begin_panic` under a branch that never returns.
+    assert_eq!(
+        contexts("_ZN5panic9may_panic17h044e5a8a5c34bdceE"),
+        vec![(
+            "_ZN3std9panicking11begin_panic17h5ae0871c3ba84f98E",
+            CallContext::Panic
+        )]
+    );
+
+    // `begin_panic` never returns, so everything on its way to panicking is
+    // unconditional.
+    assert!(
+        contexts("_ZN3std9panicking11begin_panic17h5ae0871c3ba84f98E")
+            .iter()
+            .all(|(_, context)| *context == CallContext::Unconditional)
+    );
+
+    // This `drop_in_place` frees the box both when returning and while
+    // unwinding from the call to the drop function.
+    let drop_in_place = "_ZN4core3ptr13drop_in_place17h3f8de45cb7a779d6E";
+    let box_free = "_ZN5alloc5alloc8box_free17h0dad36ae68ddb938E";
+    assert_eq!(
+        contexts(drop_in_place),
+        vec![
+            (box_free, CallContext::Unconditional),
+            (box_free, CallContext::Panic)
+        ]
+    );
+
+    // The call graph keeps the call that's most certain to be made.
+    let graph = SummaryCallGraph::new(&summaries, CallGraphOptions::default());
+    let edge = graph.inner().edge_weight(drop_in_place, box_free).unwrap();
+    assert_eq!(edge.call.context, CallContext::Unconditional);
+
+    assert_eq!(
+        contexts("simple_caller"),
+        vec![("simple_callee", CallContext::Unconditional)]
+    );
+    assert_eq!(
+        contexts("conditional_caller"),
+        vec![("simple_callee", CallContext::Conditional)]
+    );
+}
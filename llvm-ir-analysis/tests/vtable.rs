@@ -0,0 +1,174 @@
+use itertools::Itertools;
+use llvm_ir::Module;
+use llvm_ir_analysis::*;
+
+fn init_logging() {
+    // capture log messages with test harness
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+/// vtable.ll is hand-written to mirror the IR that rustc emits for trait
+/// objects; see the comment at the top of that file
+const VTABLE_BC_PATH: &'static str = "tests/bcfiles/vtable.bc";
+
+/// vtable_opaque.ll is emitted by rustc, so uses opaque pointers, which can
+/// only be read with LLVM 15 or later
+const VTABLE_OPAQUE_BC_PATH: &'static str = "tests/bcfiles/vtable_opaque.bc";
+
+#[test]
+fn vtable_call_graph() {
+    init_logging();
+    let module = Module::from_bc_path(VTABLE_BC_PATH)
+        .unwrap_or_else(|e| panic!("Failed to parse module: {}", e));
+    let analysis = ModuleAnalysis::new(&module);
+    let callgraph = analysis.call_graph();
+
+    // calls through a vtable only reach the functions in the matching slot, as
+    // long as the vtable can be told apart from any other pointer; once this
+    // file is upgraded to opaque pointers, that takes the parameter attributes
+    // that rustc emits but that are left out here (see vtable_call_graph_opaque)
+    let callees: Vec<&str> = callgraph.callees("calls_speak").sorted().collect();
+    if cfg!(feature = "llvm-14-or-lower") {
+        assert_eq!(callees, vec!["cat_speak", "dog_speak"]);
+    } else {
+        assert!(callees.contains(&"cat_speak") && callees.contains(&"dog_speak"));
+    }
+    let callees: Vec<&str> = callgraph.callees("calls_shout").sorted().collect();
+    if cfg!(feature = "llvm-14-or-lower") {
+        assert_eq!(callees, vec!["cat_shout", "dog_shout"]);
+    } else {
+        assert!(callees.contains(&"cat_shout") && callees.contains(&"dog_shout"));
+    }
+
+    let callers: Vec<&str> = callgraph.callers("cat_drop").sorted().collect();
+    assert!(callers.is_empty());
+
+    // other function pointers still fall back to matching on type
+    let callees: Vec<&str> = callgraph.callees("calls_fptr").sorted().collect();
+    assert!(callees.contains(&"unrelated"));
+    if cfg!(feature = "llvm-14-or-lower") {
+        let callers: Vec<&str> = callgraph.callers("unrelated").sorted().collect();
+        assert!(!callers.contains(&"calls_speak"));
+    }
+}
+
+#[test]
+fn vtable_offset_in_struct() {
+    init_logging();
+    let module = Module::from_bc_path(VTABLE_BC_PATH)
+        .unwrap_or_else(|e| panic!("Failed to parse module: {}", e));
+    let analysis = ModuleAnalysis::new(&module);
+    let callgraph = analysis.call_graph();
+
+    // a function pointer in a struct at the same offset as a vtable slot may
+    // still point to anything with the right type
+    let callees: Vec<&str> = callgraph.callees("calls_callback").sorted().collect();
+    assert!(callees.contains(&"unrelated"));
+    assert_eq!(
+        callgraph
+            .inner()
+            .edge_weight("calls_callback", "unrelated")
+            .map(|call| call.resolution()),
+        Some(CallResolution::Indirect)
+    );
+}
+
+#[test]
+fn vtable_slots() {
+    init_logging();
+    let module = Module::from_bc_path(VTABLE_BC_PATH)
+        .unwrap_or_else(|e| panic!("Failed to parse module: {}", e));
+    let analysis = ModuleAnalysis::new(&module);
+    let vtables = analysis.vtables();
+
+    let speak = module.get_func_by_name("dog_speak").unwrap();
+    let speak_ty = module.type_of(speak);
+    let functions: Vec<&str> = vtables
+        .functions_at_offset(24, &speak_ty)
+        .sorted()
+        .collect();
+    assert_eq!(functions, vec!["cat_speak", "dog_speak"]);
+
+    // the drop slot is still recorded, even though calls through it aren't
+    // resolved using vtables
+    let drop = module.get_func_by_name("cat_drop").unwrap();
+    let drop_ty = module.type_of(drop);
+    let functions: Vec<&str> = vtables.functions_at_offset(0, &drop_ty).collect();
+    assert_eq!(functions, vec!["cat_drop"]);
+
+    // globals that aren't named like vtables are ignored
+    let functions: Vec<&str> = vtables.functions_at_offset(24, &speak_ty).collect();
+    assert!(!functions.contains(&"unrelated"));
+}
+
+#[test]
+fn vtable_call_resolution() {
+    init_logging();
+    let module = Module::from_bc_path(VTABLE_BC_PATH)
+        .unwrap_or_else(|e| panic!("Failed to parse module: {}", e));
+    let analysis = ModuleAnalysis::new(&module);
+    let callgraph = analysis.call_graph();
+    let resolution = |caller, callee| {
+        callgraph
+            .inner()
+            .edge_weight(caller, callee)
+            .map(|call| call.resolution())
+    };
+
+    assert_eq!(
+        resolution("make_speakers", "calls_speak"),
+        Some(CallResolution::Direct)
+    );
+    assert_eq!(
+        resolution("calls_speak", "dog_speak"),
+        Some(if cfg!(feature = "llvm-14-or-lower") {
+            CallResolution::Vtable
+        } else {
+            CallResolution::Indirect
+        })
+    );
+    assert_eq!(
+        resolution("calls_fptr", "unrelated"),
+        Some(CallResolution::Indirect)
+    );
+}
+
+#[test]
+fn vtable_call_graph_opaque() {
+    init_logging();
+    if cfg!(feature = "llvm-14-or-lower") {
+        return;
+    }
+    let module = Module::from_bc_path(VTABLE_OPAQUE_BC_PATH)
+        .unwrap_or_else(|e| panic!("Failed to parse module: {}", e));
+    let analysis = ModuleAnalysis::new(&module);
+    let callgraph = analysis.call_graph();
+    let resolution = |caller, callee| {
+        callgraph
+            .inner()
+            .edge_weight(caller, callee)
+            .map(|call| call.resolution())
+    };
+
+    // a vtable passed as the metadata half of a `&dyn` argument is told apart
+    // from other pointers by its parameter attributes, so calls through it no
+    // longer reach `unrelated`, even though it has the same type as the
+    // `speak` methods and its address is taken
+    let callees: Vec<&str> = callgraph.callees("calls_speak").sorted().collect();
+    assert_eq!(callees, vec!["cat_speak", "dog_speak"]);
+    let callees: Vec<&str> = callgraph.callees("calls_shout").sorted().collect();
+    assert_eq!(callees, vec!["cat_shout", "dog_shout"]);
+    assert_eq!(
+        resolution("calls_speak", "dog_speak"),
+        Some(CallResolution::Vtable)
+    );
+
+    // a function pointer in a struct at the same offset as a vtable slot may
+    // still point to anything with the right type
+    let callees: Vec<&str> = callgraph.callees("calls_callback").sorted().collect();
+    assert!(callees.contains(&"unrelated"));
+    assert_eq!(
+        resolution("calls_callback", "unrelated"),
+        Some(CallResolution::Indirect)
+    );
+}
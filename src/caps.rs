@@ -1,10 +1,44 @@
-use std::{collections::HashMap, fmt::Debug, fs::File, path::Path};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    fs::File,
+    path::Path,
+};
 
 use capslock_rust::Function;
+use semver::{Version, VersionReq};
 use serde::Deserialize;
 
+/// The mapping of function names to the capabilities they directly require.
+///
+/// This can be deserialised from one of two JSON formats. The original format is a flat object
+/// mapping function names to their capabilities, which apply regardless of crate version:
+///
+/// ```json
+/// { "std::process::Command::new": { "caps": ["CAPABILITY_EXEC"], "syscalls": [] } }
+/// ```
+///
+/// Alternatively, functions can be grouped by crate name and semver range, with unversioned
+/// functions in a separate `functions` object:
+///
+/// ```json
+/// {
+///   "functions": { "std::process::Command::new": { "caps": ["CAPABILITY_EXEC"], "syscalls": [] } },
+///   "crates": {
+///     "tokio": {
+///       ">=1.0, <1.20": { "tokio::net::tcp::stream::TcpStream::connect": { ... } }
+///     }
+///   }
+/// }
+/// ```
 #[derive(Debug, Default, Deserialize)]
-pub struct FunctionCaps(HashMap<String, Function>);
+#[serde(from = "Raw")]
+pub struct FunctionCaps {
+    unversioned: HashMap<String, Function>,
+
+    /// Versioned functions, keyed by function name so lookups don't have to walk every crate.
+    versioned: HashMap<String, Vec<Versioned>>,
+}
 
 impl FunctionCaps {
     #[tracing::instrument(err)]
@@ -12,7 +46,206 @@ impl FunctionCaps {
         Ok(serde_json::from_reader(File::open(path.as_ref())?)?)
     }
 
-    pub fn get(&self, name: &str) -> Option<&Function> {
-        self.0.get(name)
+    /// Returns every entry for the given function name that applies within the given scope.
+    pub fn get<'a>(
+        &'a self,
+        name: &str,
+        scope: Scope<'a>,
+    ) -> impl Iterator<Item = &'a Function> + 'a {
+        self.unversioned.get(name).into_iter().chain(
+            self.versioned
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter(move |versioned| scope.matches(&versioned.krate, &versioned.req))
+                .map(|versioned| &versioned.function),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Versioned {
+    krate: String,
+    req: VersionReq,
+    function: Function,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Raw {
+    Versioned {
+        #[serde(default)]
+        functions: HashMap<String, Function>,
+        crates: HashMap<String, HashMap<VersionReq, HashMap<String, Function>>>,
+    },
+    Flat(HashMap<String, Function>),
+}
+
+impl From<Raw> for FunctionCaps {
+    fn from(raw: Raw) -> Self {
+        match raw {
+            Raw::Versioned { functions, crates } => {
+                let mut versioned: HashMap<String, Vec<Versioned>> = HashMap::new();
+
+                for (krate, ranges) in crates {
+                    for (req, functions) in ranges {
+                        for (name, function) in functions {
+                            versioned.entry(name).or_default().push(Versioned {
+                                krate: krate.clone(),
+                                req: req.clone(),
+                                function,
+                            });
+                        }
+                    }
+                }
+
+                Self {
+                    unversioned: functions,
+                    versioned,
+                }
+            }
+            Raw::Flat(unversioned) => Self {
+                unversioned,
+                versioned: HashMap::new(),
+            },
+        }
+    }
+}
+
+/// The versions of each crate that make up the build being analysed.
+#[derive(Debug, Default, Clone)]
+pub struct CrateVersions(HashMap<String, BTreeSet<Version>>);
+
+impl CrateVersions {
+    pub fn insert(&mut self, name: impl Into<String>, version: Version) {
+        self.0.entry(name.into()).or_default().insert(version);
+    }
+}
+
+/// The context that versioned function capabilities are matched within.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Scope<'a> {
+    versions: Option<&'a CrateVersions>,
+    current: Option<(&'a str, &'a Version)>,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(versions: &'a CrateVersions) -> Self {
+        Self {
+            versions: Some(versions),
+            current: None,
+        }
+    }
+
+    /// Sets the crate that owns the code currently being analysed.
+    ///
+    /// When matching entries for this crate, only this version will be considered, even if the
+    /// build includes other versions of the same crate.
+    pub fn with_current(self, name: &'a str, version: &'a Version) -> Self {
+        Self {
+            current: Some((name, version)),
+            ..self
+        }
+    }
+
+    fn matches(&self, krate: &str, req: &VersionReq) -> bool {
+        match self.current {
+            Some((name, version)) if name == krate => req.matches(version),
+            _ => self
+                .versions
+                .and_then(|versions| versions.0.get(krate))
+                .is_some_and(|versions| versions.iter().any(|version| req.matches(version))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use capslock::Capability;
+
+    use super::*;
+
+    fn caps<'a>(function_caps: &'a FunctionCaps, name: &str, scope: Scope<'a>) -> Vec<Capability> {
+        function_caps
+            .get(name, scope)
+            .flat_map(|function| function.caps.iter().copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn flat() -> anyhow::Result<()> {
+        let function_caps: FunctionCaps = serde_json::from_str(
+            r#"{ "std::process::Command::new": { "caps": ["CAPABILITY_EXEC"], "syscalls": [] } }"#,
+        )?;
+
+        assert_eq!(
+            caps(
+                &function_caps,
+                "std::process::Command::new",
+                Scope::default()
+            ),
+            vec![Capability::Exec]
+        );
+        assert!(caps(&function_caps, "std::fs::File::open", Scope::default()).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn versioned() -> anyhow::Result<()> {
+        let function_caps: FunctionCaps = serde_json::from_str(
+            r#"{
+                "functions": {
+                    "std::process::Command::new": { "caps": ["CAPABILITY_EXEC"], "syscalls": [] }
+                },
+                "crates": {
+                    "foo": {
+                        "<1.0": { "foo::old::connect": { "caps": ["CAPABILITY_NETWORK"], "syscalls": [] } },
+                        "^1.0": { "foo::new::connect": { "caps": ["CAPABILITY_NETWORK"], "syscalls": [] } }
+                    }
+                }
+            }"#,
+        )?;
+
+        let mut versions = CrateVersions::default();
+        versions.insert("foo", Version::new(0, 9, 0));
+        versions.insert("foo", Version::new(1, 2, 0));
+
+        // Unversioned functions always apply.
+        assert_eq!(
+            caps(
+                &function_caps,
+                "std::process::Command::new",
+                Scope::new(&versions)
+            ),
+            vec![Capability::Exec]
+        );
+
+        // Both versions of foo are in the build, so both entries apply to outside callers.
+        let scope = Scope::new(&versions);
+        assert_eq!(
+            caps(&function_caps, "foo::old::connect", scope),
+            vec![Capability::Network]
+        );
+        assert_eq!(
+            caps(&function_caps, "foo::new::connect", scope),
+            vec![Capability::Network]
+        );
+
+        // Within foo itself, only the entries for that version apply.
+        let version = Version::new(1, 2, 0);
+        let scope = Scope::new(&versions).with_current("foo", &version);
+        assert!(caps(&function_caps, "foo::old::connect", scope).is_empty());
+        assert_eq!(
+            caps(&function_caps, "foo::new::connect", scope),
+            vec![Capability::Network]
+        );
+
+        // If foo isn't in the build at all, nothing applies.
+        assert!(caps(&function_caps, "foo::new::connect", Scope::default()).is_empty());
+
+        Ok(())
     }
 }
@@ -11,7 +11,10 @@ use symbolic::{
 };
 use thiserror::Error;
 
-use crate::{caps::FunctionCaps, location::IntoOptionLocation};
+use crate::{
+    caps::{FunctionCaps, Scope},
+    location::IntoOptionLocation,
+};
 
 #[derive(Default, Debug)]
 #[cfg_attr(test, derive(serde::Serialize))]
@@ -40,11 +43,12 @@ impl FunctionMap {
     pub fn upsert_with_caps(
         &mut self,
         function_caps: &FunctionCaps,
+        scope: Scope,
         function: impl ToFunction,
     ) -> Result<usize, Error> {
         Ok(self.upsert(
             function.mangled_name(),
-            function.to_function_with_fn_caps(function_caps)?,
+            function.to_function_with_fn_caps(function_caps, scope)?,
         ))
     }
 
@@ -141,6 +145,10 @@ pub trait ToFunction {
             location: self.debugloc().into_option_location(),
             capabilities: BTreeMap::new(),
             syscalls: BTreeSet::new(),
+            package: None,
+            witnesses: BTreeMap::new(),
+            paths: BTreeMap::new(),
+            evidence: Vec::new(),
         })
     }
 
@@ -153,21 +161,30 @@ pub trait ToFunction {
             location: self.debugloc().into_option_location(),
             capabilities: caps.collect(),
             syscalls: BTreeSet::new(),
+            package: None,
+            witnesses: BTreeMap::new(),
+            paths: BTreeMap::new(),
+            evidence: Vec::new(),
         })
     }
 
     fn to_function_with_fn_caps(
         &self,
         function_caps: &FunctionCaps,
+        scope: Scope,
     ) -> Result<report::Function, Error> {
         let name = parse_mangled_name(self.mangled_name())?;
-        let capabilities = direct_fn_caps(function_caps, &name);
+        let capabilities = direct_fn_caps(function_caps, scope, &name);
 
         Ok(report::Function {
             name,
             location: self.debugloc().into_option_location(),
             capabilities,
             syscalls: BTreeSet::new(),
+            package: None,
+            witnesses: BTreeMap::new(),
+            paths: BTreeMap::new(),
+            evidence: Vec::new(),
         })
     }
 }
@@ -202,18 +219,18 @@ impl<'a> ToFunction for Name<'a> {
     }
 }
 
-fn direct_fn_caps(
+/// Returns the direct capabilities of the named function according to the function capability
+/// mapping.
+pub fn direct_fn_caps(
     function_caps: &FunctionCaps,
+    scope: Scope,
     name: &FunctionName,
 ) -> BTreeMap<Capability, CapabilityType> {
-    if let Some(caps) = function_caps.get(name.display_name()) {
-        caps.caps
-            .iter()
-            .map(|cap| (*cap, CapabilityType::Direct))
-            .collect()
-    } else {
-        BTreeMap::new()
-    }
+    function_caps
+        .get(name.display_name(), scope)
+        .flat_map(|caps| caps.caps.iter())
+        .map(|cap| (*cap, CapabilityType::Direct))
+        .collect()
 }
 
 #[cfg(test)]
@@ -1,19 +1,50 @@
 use std::{
-    collections::BTreeSet,
+    collections::{HashSet, VecDeque},
     fmt::Debug,
     ops::{Deref, DerefMut},
 };
 
 use capslock::{
-    CapabilityType,
-    report::{Edge, Location},
+    Capability, CapabilityType,
+    report::{CallContext, Edge, EdgeKind, Location, Witness},
 };
-use petgraph::prelude::DiGraphMap;
+use clap::ValueEnum;
+use petgraph::{Direction, prelude::DiGraphMap};
+use strum::IntoEnumIterator;
 
 use crate::function::FunctionMap;
 
 #[derive(Default)]
-pub struct CallGraph(DiGraphMap<usize, Option<Location>>);
+pub struct CallGraph(DiGraphMap<usize, CallSite>);
+
+/// Details of a call from one function to another.
+#[derive(Debug, Default, Clone)]
+pub struct CallSite {
+    pub location: Option<Location>,
+    /// How the callee was determined, or `None` if the call was observed directly.
+    pub kind: Option<EdgeKind>,
+    /// Whether the call may unwind into a landing pad in the caller.
+    pub invoke: bool,
+    /// Under what circumstances the call is made, or `None` if the call was observed directly.
+    pub context: Option<CallContext>,
+}
+
+impl CallSite {
+    fn is_speculative(&self) -> bool {
+        self.kind.is_some_and(|kind| kind.is_speculative())
+    }
+}
+
+/// The edges that transitive capabilities are propagated along.
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Propagation {
+    /// Propagate along every edge, giving the capabilities each function may have.
+    #[default]
+    May,
+    /// Ignore speculative edges, such as calls through function pointers, giving the capabilities
+    /// each function must have.
+    Must,
+}
 
 impl Debug for CallGraph {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -25,36 +56,230 @@ impl Debug for CallGraph {
 }
 
 impl CallGraph {
+    /// Adds each capability of every function to each of its transitive callers as a transitive
+    /// capability, following only the edges selected by `propagation`.
+    ///
+    /// Functions within a strongly connected component can all reach each other, so they always
+    /// end up with the same capabilities. We condense the graph into its components, ordered so
+    /// that callees come before their callers, and then only need to visit each component and
+    /// edge once.
     #[tracing::instrument(skip_all)]
-    pub fn bubble_transitive_capabilities(&self, functions: &mut FunctionMap) {
-        // This is about the stupidest possible way to do this, but hey, I have a film degree.
-        let mut changed = true;
-        while changed {
-            changed = false;
-
-            for (caller, callee, _) in self.0.all_edges() {
-                let callee_caps = functions
-                    .get(callee)
-                    .unwrap()
-                    .capabilities
-                    .keys()
-                    .copied()
-                    .collect::<BTreeSet<_>>();
-                let caller = functions.get_mut(caller).unwrap();
-
-                for cap in callee_caps.iter() {
-                    if !caller.capabilities.contains_key(cap) {
-                        caller.capabilities.insert(*cap, CapabilityType::Transitive);
-                        changed = true;
+    pub fn bubble_transitive_capabilities(
+        &self,
+        functions: &mut FunctionMap,
+        propagation: Propagation,
+    ) {
+        // Looking up nodes and edges in the graph map means hashing, so we work with a dense
+        // adjacency list indexed by function instead.
+        let len = self.0.nodes().max().map_or(0, |max| max + 1);
+        let mut callees = vec![Vec::new(); len];
+        let mut callers = vec![Vec::new(); len];
+        for (caller, callee, call) in self
+            .0
+            .all_edges()
+            .filter(|(_, _, call)| propagation == Propagation::May || !call.is_speculative())
+        {
+            callees[caller].push(callee);
+            callers[callee].push((caller, call));
+        }
+
+        let components = components(&callees);
+        let mut component_of = vec![0; len];
+        for (component, members) in components.iter().enumerate() {
+            for member in members.iter() {
+                component_of[*member] = component;
+            }
+        }
+
+        let mut caps = vec![CapabilitySet::default(); components.len()];
+        for (component, members) in components.iter().enumerate() {
+            let mut set = CapabilitySet::default();
+            for member in members.iter() {
+                if let Some(function) = functions.get(*member) {
+                    set.extend(function.capabilities.keys().copied());
+                }
+
+                // Callee components always precede their callers, so this is either already
+                // complete or the component we're building.
+                for callee in callees[*member].iter() {
+                    set.union(caps[component_of[*callee]]);
+                }
+            }
+            caps[component] = set;
+
+            for member in members.iter() {
+                let Some(function) = functions.get_mut(*member) else {
+                    continue;
+                };
+                for cap in set.iter() {
+                    function
+                        .capabilities
+                        .entry(cap)
+                        .or_insert(CapabilityType::Transitive);
+                }
+            }
+        }
+
+        record_witnesses(functions, &callers);
+    }
+}
+
+/// Records the witness for each transitive capability of each function: the callee on a shortest
+/// path to a function with that capability directly.
+///
+/// This is a breadth first search backwards along the call graph from every function with the
+/// capability directly, so the first callee a function is reached from is always a closest one.
+fn record_witnesses(functions: &mut FunctionMap, callers: &[Vec<(usize, &CallSite)>]) {
+    for cap in Capability::iter().filter(|cap| cap != &Capability::Safe) {
+        let mut visited = vec![false; callers.len()];
+        let mut queue = VecDeque::new();
+        for (idx, seen) in visited.iter_mut().enumerate() {
+            if functions
+                .get(idx)
+                .and_then(|func| func.capabilities.get(&cap))
+                .is_some_and(|ty| ty != &CapabilityType::Transitive)
+            {
+                *seen = true;
+                queue.push_back(idx);
+            }
+        }
+
+        while let Some(callee) = queue.pop_front() {
+            for (caller, call) in callers[callee].iter() {
+                if visited[*caller] {
+                    continue;
+                }
+                visited[*caller] = true;
+                queue.push_back(*caller);
+
+                if let Some(function) = functions.get_mut(*caller) {
+                    function.witnesses.insert(
+                        cap,
+                        Witness {
+                            callee,
+                            location: call.location.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Returns the strongly connected components of the graph with the given adjacency list, with
+/// each component preceding any component with an edge into it.
+///
+/// This is Tarjan's algorithm, but iterative rather than recursive: call chains in large programs
+/// are easily deep enough to overflow the stack otherwise.
+fn components(callees: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+
+    let mut index = vec![UNVISITED; callees.len()];
+    let mut lowlink = vec![0; callees.len()];
+    let mut on_stack = vec![false; callees.len()];
+    let mut next = 0;
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+
+    for root in 0..callees.len() {
+        if index[root] != UNVISITED {
+            continue;
+        }
+
+        // Each frame is a node being visited, along with how many of its callees we've visited.
+        let mut calls = vec![(root, 0)];
+        index[root] = next;
+        lowlink[root] = next;
+        next += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((node, visited)) = calls.last_mut() {
+            let node = *node;
+            if let Some(&callee) = callees[node].get(*visited) {
+                *visited += 1;
+                if index[callee] == UNVISITED {
+                    index[callee] = next;
+                    lowlink[callee] = next;
+                    next += 1;
+                    stack.push(callee);
+                    on_stack[callee] = true;
+                    calls.push((callee, 0));
+                } else if on_stack[callee] {
+                    lowlink[node] = lowlink[node].min(index[callee]);
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some((caller, _)) = calls.last() {
+                lowlink[*caller] = lowlink[*caller].min(lowlink[node]);
+            }
+
+            if index[node] == lowlink[node] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
                     }
                 }
+                components.push(component);
             }
         }
     }
+
+    components
+}
+
+/// A set of capabilities, represented as a bitset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct CapabilitySet(u64);
+
+impl CapabilitySet {
+    fn bit(cap: Capability) -> u64 {
+        1 << (cap as u32)
+    }
+
+    fn extend(&mut self, caps: impl IntoIterator<Item = Capability>) {
+        for cap in caps {
+            self.0 |= Self::bit(cap);
+        }
+    }
+
+    fn union(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    fn iter(self) -> impl Iterator<Item = Capability> {
+        Capability::iter().filter(move |cap| self.0 & Self::bit(*cap) != 0)
+    }
+}
+
+impl CallGraph {
+    /// Returns the functions reachable from the given entry points, including the entry points
+    /// themselves.
+    pub fn reachable(&self, entry_points: impl IntoIterator<Item = usize>) -> HashSet<usize> {
+        let mut reachable = HashSet::new();
+        let mut stack = entry_points.into_iter().collect::<Vec<_>>();
+
+        while let Some(idx) = stack.pop() {
+            if reachable.insert(idx) {
+                stack.extend(
+                    self.0
+                        .neighbors_directed(idx, Direction::Outgoing)
+                        .filter(|callee| !reachable.contains(callee)),
+                );
+            }
+        }
+
+        reachable
+    }
 }
 
 impl Deref for CallGraph {
-    type Target = DiGraphMap<usize, Option<Location>>;
+    type Target = DiGraphMap<usize, CallSite>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -67,8 +292,8 @@ impl DerefMut for CallGraph {
     }
 }
 
-impl From<DiGraphMap<usize, Option<Location>>> for CallGraph {
-    fn from(value: DiGraphMap<usize, Option<Location>>) -> Self {
+impl From<DiGraphMap<usize, CallSite>> for CallGraph {
+    fn from(value: DiGraphMap<usize, CallSite>) -> Self {
         Self(value)
     }
 }
@@ -77,10 +302,13 @@ impl From<CallGraph> for Vec<Edge> {
     fn from(call_graph: CallGraph) -> Self {
         call_graph
             .all_edges()
-            .map(|(caller, callee, location)| Edge {
+            .map(|(caller, callee, call)| Edge {
                 caller,
                 callee,
-                location: location.clone(),
+                location: call.location.clone(),
+                kind: call.kind,
+                invoke: call.invoke,
+                context: call.context,
             })
             .collect()
     }
@@ -88,7 +316,7 @@ impl From<CallGraph> for Vec<Edge> {
 
 #[cfg(test)]
 mod tests {
-    use capslock::Capability;
+    use std::collections::btree_map::Entry;
 
     use crate::function::ToFunction;
 
@@ -132,12 +360,12 @@ mod tests {
         );
         let e = functions.upsert("e", TestFunction("e").to_function()?);
 
-        graph.add_edge(a, b, None);
-        graph.add_edge(b, d, None);
-        graph.add_edge(a, c, None);
-        graph.add_edge(c, e, None);
+        graph.add_edge(a, b, CallSite::default());
+        graph.add_edge(b, d, CallSite::default());
+        graph.add_edge(a, c, CallSite::default());
+        graph.add_edge(c, e, CallSite::default());
 
-        graph.bubble_transitive_capabilities(&mut functions);
+        graph.bubble_transitive_capabilities(&mut functions, Propagation::May);
 
         insta::with_settings!({ sort_maps => true }, {
             insta::assert_yaml_snapshot!(functions);
@@ -145,4 +373,297 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn must() -> anyhow::Result<()> {
+        // `a` calls `b` directly and `c` through a function pointer, and both have a direct
+        // capability. Only `b`'s should be propagated to `a` when ignoring speculative edges.
+        let setup = || -> anyhow::Result<(FunctionMap, CallGraph)> {
+            let mut functions = FunctionMap::default();
+            let mut graph = CallGraph::default();
+
+            let a = functions.upsert("a", TestFunction("a").to_function()?);
+            let b = functions.upsert(
+                "b",
+                TestFunction("b").to_function_with_caps(
+                    [(Capability::Files, CapabilityType::Direct)].into_iter(),
+                )?,
+            );
+            let c = functions.upsert(
+                "c",
+                TestFunction("c").to_function_with_caps(
+                    [(Capability::Network, CapabilityType::Direct)].into_iter(),
+                )?,
+            );
+
+            let call = |kind| CallSite {
+                kind: Some(kind),
+                ..Default::default()
+            };
+            graph.add_edge(a, b, call(EdgeKind::Direct));
+            graph.add_edge(a, c, call(EdgeKind::Indirect));
+
+            Ok((functions, graph))
+        };
+        let caps = |propagation| -> anyhow::Result<Vec<Capability>> {
+            let (mut functions, graph) = setup()?;
+            graph.bubble_transitive_capabilities(&mut functions, propagation);
+
+            let a = functions.get_index("a").unwrap();
+            Ok(functions
+                .get(a)
+                .unwrap()
+                .capabilities
+                .keys()
+                .copied()
+                .collect())
+        };
+
+        assert_eq!(
+            caps(Propagation::May)?,
+            vec![Capability::Files, Capability::Network]
+        );
+        assert_eq!(caps(Propagation::Must)?, vec![Capability::Files]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cycle() -> anyhow::Result<()> {
+        let mut functions = FunctionMap::default();
+        let mut graph = CallGraph::default();
+
+        // a -> b -> c -> b, with b and c each having a different direct capability. Both should
+        // end up with each other's, and a with both.
+        let a = functions.upsert("a", TestFunction("a").to_function()?);
+        let b = functions.upsert(
+            "b",
+            TestFunction("b")
+                .to_function_with_caps([(Capability::Files, CapabilityType::Direct)].into_iter())?,
+        );
+        let c = functions.upsert(
+            "c",
+            TestFunction("c").to_function_with_caps(
+                [(Capability::Network, CapabilityType::Direct)].into_iter(),
+            )?,
+        );
+
+        graph.add_edge(a, b, CallSite::default());
+        graph.add_edge(b, c, CallSite::default());
+        graph.add_edge(c, b, CallSite::default());
+
+        graph.bubble_transitive_capabilities(&mut functions, Propagation::May);
+
+        let caps = |idx| functions.get(idx).unwrap().capabilities.clone();
+        assert_eq!(
+            caps(a),
+            [
+                (Capability::Files, CapabilityType::Transitive),
+                (Capability::Network, CapabilityType::Transitive),
+            ]
+            .into()
+        );
+        assert_eq!(
+            caps(b),
+            [
+                (Capability::Files, CapabilityType::Direct),
+                (Capability::Network, CapabilityType::Transitive),
+            ]
+            .into()
+        );
+        assert_eq!(
+            caps(c),
+            [
+                (Capability::Files, CapabilityType::Transitive),
+                (Capability::Network, CapabilityType::Direct),
+            ]
+            .into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn witnesses() -> anyhow::Result<()> {
+        let mut functions = FunctionMap::default();
+        let mut graph = CallGraph::default();
+
+        // a -> b -> c -> d, plus a shortcut a -> d, with d having a direct capability. The
+        // witness for `a` should be the shortcut, and a path should be recorded for each caller.
+        let a = functions.upsert("a", TestFunction("a").to_function()?);
+        let b = functions.upsert("b", TestFunction("b").to_function()?);
+        let c = functions.upsert("c", TestFunction("c").to_function()?);
+        let d = functions.upsert(
+            "d",
+            TestFunction("d")
+                .to_function_with_caps([(Capability::Exec, CapabilityType::Direct)].into_iter())?,
+        );
+
+        let call = |line| CallSite {
+            location: Some(Location {
+                directory: None,
+                filename: "src/lib.rs".into(),
+                line,
+                column: None,
+            }),
+            ..Default::default()
+        };
+        graph.add_edge(a, b, call(1));
+        graph.add_edge(b, c, call(2));
+        graph.add_edge(c, d, call(3));
+        graph.add_edge(a, d, call(4));
+
+        graph.bubble_transitive_capabilities(&mut functions, Propagation::May);
+
+        let witness = |idx| {
+            functions
+                .get(idx)
+                .unwrap()
+                .witnesses
+                .get(&Capability::Exec)
+                .map(|witness| (witness.callee, witness.location.as_ref().unwrap().line))
+        };
+        assert_eq!(witness(a), Some((d, 4)));
+        assert_eq!(witness(b), Some((c, 2)));
+        assert_eq!(witness(c), Some((d, 3)));
+        assert_eq!(witness(d), None);
+
+        Ok(())
+    }
+
+    /// Builds a pseudo-random call graph with the given number of functions, with roughly one in
+    /// ten having a direct capability, and one in five calls being speculative.
+    fn random_graph(size: usize, calls: usize) -> anyhow::Result<(FunctionMap, CallGraph)> {
+        // A fixed xorshift generator keeps this reproducible without pulling in another crate.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+
+        let caps = [
+            Capability::Files,
+            Capability::Network,
+            Capability::ReadSystemState,
+            Capability::Exec,
+        ];
+        let mut functions = FunctionMap::default();
+        for i in 0..size {
+            let name = format!("f{i}");
+            let function = match next(10) {
+                0 => TestFunction("").to_function_with_caps(
+                    [(caps[next(caps.len())], CapabilityType::Direct)].into_iter(),
+                )?,
+                _ => TestFunction("").to_function()?,
+            };
+            functions.upsert(&name, function);
+        }
+
+        let mut graph = CallGraph::default();
+        for _ in 0..calls {
+            let kind = match next(5) {
+                0 => EdgeKind::Indirect,
+                _ => EdgeKind::Direct,
+            };
+            graph.add_edge(
+                next(size),
+                next(size),
+                CallSite {
+                    kind: Some(kind),
+                    ..Default::default()
+                },
+            );
+        }
+
+        Ok((functions, graph))
+    }
+
+    #[test]
+    fn matches_fixpoint() -> anyhow::Result<()> {
+        // The original implementation, which just iterates over every edge until nothing changes.
+        fn fixpoint(graph: &CallGraph, functions: &mut FunctionMap, propagation: Propagation) {
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for (caller, callee, _) in graph.all_edges().filter(|(_, _, call)| {
+                    propagation == Propagation::May || !call.is_speculative()
+                }) {
+                    let callee_caps = functions
+                        .get(callee)
+                        .unwrap()
+                        .capabilities
+                        .keys()
+                        .copied()
+                        .collect::<Vec<_>>();
+                    let caller = functions.get_mut(caller).unwrap();
+                    for cap in callee_caps {
+                        if let Entry::Vacant(entry) = caller.capabilities.entry(cap) {
+                            entry.insert(CapabilityType::Transitive);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        for propagation in [Propagation::May, Propagation::Must] {
+            let (mut expected, graph) = random_graph(2_000, 3_000)?;
+            fixpoint(&graph, &mut expected, propagation);
+
+            let (mut actual, graph) = random_graph(2_000, 3_000)?;
+            graph.bubble_transitive_capabilities(&mut actual, propagation);
+
+            let caps = |functions: FunctionMap| {
+                functions
+                    .into_functions()
+                    .into_iter()
+                    .map(|func| func.capabilities)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(caps(actual), caps(expected), "{propagation:?}");
+        }
+
+        Ok(())
+    }
+
+    /// Times propagation over a graph about the size of a large workspace. Run with:
+    ///
+    /// ```sh
+    /// cargo test --release -- --ignored --nocapture bench_bubble
+    /// ```
+    #[test]
+    #[ignore]
+    fn bench_bubble() -> anyhow::Result<()> {
+        for propagation in [Propagation::May, Propagation::Must] {
+            let (mut functions, graph) = random_graph(400_000, 2_000_000)?;
+
+            let start = std::time::Instant::now();
+            graph.bubble_transitive_capabilities(&mut functions, propagation);
+            eprintln!("{propagation:?}: {:?}", start.elapsed());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn reachable() {
+        let mut graph = CallGraph::default();
+
+        // a -> b -> c, with a cycle back to b, and an isolated d -> e.
+        graph.add_edge(0, 1, CallSite::default());
+        graph.add_edge(1, 2, CallSite::default());
+        graph.add_edge(2, 1, CallSite::default());
+        graph.add_edge(3, 4, CallSite::default());
+
+        let mut reachable = graph.reachable([0]).into_iter().collect::<Vec<_>>();
+        reachable.sort();
+        assert_eq!(reachable, vec![0, 1, 2]);
+
+        // Entry points that aren't in the graph at all are still reachable.
+        let mut reachable = graph.reachable([4, 5]).into_iter().collect::<Vec<_>>();
+        reachable.sort();
+        assert_eq!(reachable, vec![4, 5]);
+    }
 }
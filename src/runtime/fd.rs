@@ -136,4 +136,16 @@ impl Type {
         // Otherwise, we'll just punt.
         Self::Unknown
     }
+
+    /// Returns true if this is a file that looks like a shared object, such as `libfoo.so` or
+    /// `libfoo.so.1.2`.
+    pub fn is_shared_object(&self) -> bool {
+        match self {
+            Self::File { path } => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with(".so") || name.contains(".so.")),
+            _ => false,
+        }
+    }
 }
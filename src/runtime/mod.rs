@@ -7,7 +7,7 @@ use std::{
     process::Command,
 };
 
-use capslock::CapabilityType;
+use capslock::{CapabilityType, report};
 use clap::Parser;
 use nix::unistd::Pid;
 use ptrace_iterator::{
@@ -69,8 +69,32 @@ pub struct Runtime {
 impl Runtime {
     #[tracing::instrument(err)]
     pub fn main(self) -> Result<(), Error> {
+        let (report, code) = self.trace()?;
+
+        // Output the Capslock JSON.
+        let mut writer: Box<dyn Write> = if let Some(output) = self.output {
+            Box::new(File::create(&output).map_err(|e| Error::OutputCreate {
+                e,
+                path: output.to_path_buf(),
+            })?)
+        } else {
+            Box::new(std::io::stdout())
+        };
+        serde_json::to_writer_pretty(&mut writer, &report)?;
+
+        // Do our best to forward on the child's exit status.
+        if let Some(code) = code {
+            std::process::exit(code);
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs and traces the command, returning the report and the command's exit code, if it
+    /// exited with one.
+    fn trace(&self) -> Result<(report::Report, Option<i32>), Error> {
         // Wrangle argv and extract the command path.
-        let mut argv = self.argv.into_iter().collect::<VecDeque<_>>();
+        let mut argv = self.argv.iter().cloned().collect::<VecDeque<_>>();
         let path = argv.pop_front().ok_or(Error::Argv0)?;
 
         // Spawn the command we're going to trace.
@@ -89,7 +113,7 @@ impl Runtime {
             if self.include_before_start {
                 StartBehaviour::IncludeAll
             } else {
-                StartBehaviour::OnlyAfter(self.start_symbol)
+                StartBehaviour::OnlyAfter(self.start_symbol.clone())
             },
             self.include_syscalls,
             self.lookup_locations,
@@ -114,31 +138,17 @@ impl Runtime {
         // Stop forwarding signals, since there's no longer a child process.
         drop(signal_forwarder);
 
-        // Output the Capslock JSON.
-        let mut writer: Box<dyn Write> = if let Some(output) = self.output {
-            Box::new(File::create(&output).map_err(|e| Error::OutputCreate {
-                e,
-                path: output.to_path_buf(),
-            })?)
-        } else {
-            Box::new(std::io::stdout())
-        };
-        serde_json::to_writer_pretty(
-            &mut writer,
-            &global_state.processes.into_report(self.include_children)?,
-        )?;
-
-        // Do our best to forward on the child's exit status.
-        if let Some(status) = tracer.status()
-            && let Some(code) = status.code()
-        {
-            std::process::exit(code);
-        } else {
-            Ok(())
-        }
+        Ok((
+            global_state.processes.into_report(self.include_children)?,
+            tracer.status().as_ref().and_then(|status| status.code()),
+        ))
     }
 }
 
+/// Functions that are only on the stack once the dynamic loader has handed over to the program,
+/// for programs linked against either glibc or musl.
+const ENTRY_SYMBOLS: &[&str] = &["__libc_start_main", "__libc_start_call_main", "main"];
+
 /// Global state while analysing a tree of running processes.
 struct GlobalState {
     processes: process::Map,
@@ -227,6 +237,52 @@ impl GlobalState {
             .get_mut_active(pid)
             .ok_or(Error::ProcessUnknown(pid))?;
 
+        // Walk the stack before working out the syscall's capabilities, since the first syscall
+        // with the program's entry point on the stack is already past the dynamic loader.
+        let mut start = false;
+        let address_spaces = &mut self.address_spaces;
+        let names = (|| -> Result<_, Error> {
+            // Configure libunwind to use ptrace to access the child's memory space.
+            let state = PTraceState::new(pid.as_raw() as u32)?;
+            let address_space = address_spaces.entry(pid).or_insert_with(|| {
+                AddressSpace::new(Accessors::ptrace(), Byteorder::DEFAULT).unwrap()
+            });
+            let mut cursor = Cursor::remote(address_space, &state)?;
+
+            let mut names = Vec::new();
+            loop {
+                let Ok(ip) = cursor.register(RegNum::IP) else {
+                    return Ok(None);
+                };
+
+                if let Ok(name) = cursor.procedure_name()
+                    && let Ok(info) = cursor.procedure_info()
+                    && ip == info.start_ip() + name.offset()
+                {
+                    if ENTRY_SYMBOLS.contains(&name.name()) {
+                        process_state.entry_seen();
+                    }
+
+                    if let Some(after) = process_state.get_start_symbol()
+                        && name.name() == after
+                    {
+                        start = true;
+                        return Ok(None);
+                    }
+
+                    names.push(name);
+                }
+
+                // On to the next stack frame!
+                match cursor.step() {
+                    Ok(true) => continue,
+                    Ok(false) | Err(_) => break,
+                }
+            }
+
+            Ok(Some(names))
+        })();
+
         // Even if we can't get a stack trace, let's minimally update the overall set of
         // capabilities.
         let syscall_caps = meta.into_capabilities(process_state, event.sval())?;
@@ -234,42 +290,15 @@ impl GlobalState {
             process_state.extend_caps(syscall_caps.iter().copied());
         }
 
-        // Configure libunwind to use ptrace to access the child's memory space.
-        let state = PTraceState::new(pid.as_raw() as u32)?;
-        let address_space = self
-            .address_spaces
-            .entry(pid)
-            .or_insert_with(|| AddressSpace::new(Accessors::ptrace(), Byteorder::DEFAULT).unwrap());
-        let mut cursor = Cursor::remote(address_space, &state)?;
-
-        let mut names = Vec::new();
-        loop {
-            let Ok(ip) = cursor.register(RegNum::IP) else {
-                return Ok(());
-            };
-
-            if let Ok(name) = cursor.procedure_name()
-                && let Ok(info) = cursor.procedure_info()
-                && ip == info.start_ip() + name.offset()
-            {
-                if let Some(after) = process_state.get_start_symbol()
-                    && name.name() == after
-                {
-                    process_state.start_seen();
-
-                    // We still want to ignore this one, though.
-                    return Ok(());
-                }
-
-                names.push(name);
-            }
+        if start {
+            process_state.start_seen();
 
-            // On to the next stack frame!
-            match cursor.step() {
-                Ok(true) => continue,
-                Ok(false) | Err(_) => break,
-            }
+            // We still want to ignore this one, though.
+            return Ok(());
         }
+        let Some(names) = names? else {
+            return Ok(());
+        };
 
         if !process_state.is_waiting_for_start() {
             let mut child_idx = None;
@@ -320,3 +349,35 @@ impl GlobalState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use capslock::Capability;
+
+    use super::*;
+
+    /// Traces a real, dynamically linked `echo`, so this needs permission to use ptrace. Run with:
+    ///
+    /// ```sh
+    /// cargo test -- --ignored startup_is_not_dynamic_loading
+    /// ```
+    #[test]
+    #[ignore]
+    fn startup_is_not_dynamic_loading() -> anyhow::Result<()> {
+        // Everything before the start symbol is included, since that's when ld.so maps the shared
+        // objects the program is linked against.
+        let runtime =
+            Runtime::try_parse_from(["runtime", "--include-before-start", "echo", "hello"])?;
+        let (report, code) = runtime.trace()?;
+        assert_eq!(code, Some(0));
+        assert!(!report.process.capabilities.is_empty());
+        assert!(
+            !report
+                .process
+                .capabilities
+                .contains(&Capability::DynamicLoading)
+        );
+
+        Ok(())
+    }
+}
@@ -12,7 +12,7 @@ use ptrace_iterator::core::Fd;
 
 use crate::{
     function::FunctionMap,
-    graph::CallGraph,
+    graph::{CallGraph, CallSite},
     runtime::{StartBehaviour, error::Error, fd},
 };
 
@@ -39,6 +39,7 @@ impl Map {
                 init_pid,
                 State {
                     execs: [init_exec].into_iter().collect(),
+                    entered: false,
                     fds: Default::default(),
                     pid: init_pid,
                     waiting_for_start: start_behaviour.waiting_for_start_default(),
@@ -82,6 +83,7 @@ impl Map {
             child,
             State {
                 execs: Default::default(),
+                entered: parent.entered,
                 fds: parent
                     .fds
                     .iter()
@@ -124,6 +126,7 @@ impl Map {
             } else {
                 Vec::new()
             },
+            metadata: None,
         })
     }
 
@@ -138,6 +141,9 @@ impl Map {
 #[derive(Debug)]
 pub struct State {
     execs: VecDeque<Exec>,
+    /// Whether the current program has been entered, which means the dynamic loader has finished
+    /// loading everything it was linked against.
+    entered: bool,
     fds: BTreeMap<Fd, fd::Meta>,
     pid: Pid,
     start_behaviour: Arc<StartBehaviour>,
@@ -151,11 +157,14 @@ pub struct State {
 
 impl State {
     pub fn add_edge(&mut self, from: usize, to: usize) {
-        self.call_graph.add_edge(from, to, None);
+        self.call_graph.add_edge(from, to, CallSite::default());
     }
 
     pub fn add_exec(&mut self, exec: Exec) {
         self.execs.push_back(exec);
+
+        // The new program has to be loaded all over again.
+        self.entered = false;
     }
 
     pub fn close(&mut self, fd: Fd) {
@@ -170,6 +179,10 @@ impl State {
         self.caps.extend(caps);
     }
 
+    pub fn entry_seen(&mut self) {
+        self.entered = true;
+    }
+
     pub fn get_fd(&self, fd: Fd) -> Option<&fd::Meta> {
         self.fds.get(&fd)
     }
@@ -193,6 +206,10 @@ impl State {
         self.fds.insert(fd, meta);
     }
 
+    pub fn has_entered(&self) -> bool {
+        self.entered
+    }
+
     pub fn is_waiting_for_start(&self) -> bool {
         self.waiting_for_start
     }
@@ -224,9 +241,13 @@ impl State {
             } else {
                 PathBuf::new()
             },
+            role: None,
             capabilities: self.caps,
             functions: self.functions.into_functions(),
             edges: self.call_graph.into(),
+            packages: Vec::new(),
+            unreachable: Vec::new(),
+            api: Vec::new(),
         }
     }
 }
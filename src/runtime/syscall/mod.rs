@@ -1,6 +1,6 @@
 use std::{
     collections::BTreeSet,
-    ffi::{OsString, c_ulong},
+    ffi::{OsString, c_int, c_ulong},
     ops::RangeInclusive,
     path::PathBuf,
 };
@@ -52,13 +52,18 @@ enum Typed {
         cmd: c_ulong,
         fd: Fd,
     },
+    Mmap {
+        fd: Fd,
+        prot: c_int,
+    },
 }
 
 impl Meta {
     #[tracing::instrument(level="TRACE", skip(state), err, fields(pid = %state.pid()))]
     pub fn try_from_syscall(state: &mut process::State, syscall: &Syscall) -> Result<Self, Error> {
         use nix::libc::{
-            SOCK_CLOEXEC, SOCK_DGRAM, SOCK_RAW, SOCK_RDM, SOCK_SEQPACKET, SOCK_STREAM,
+            MAP_ANONYMOUS, SOCK_CLOEXEC, SOCK_DGRAM, SOCK_RAW, SOCK_RDM, SOCK_SEQPACKET,
+            SOCK_STREAM,
         };
 
         let pid = state.pid();
@@ -132,6 +137,13 @@ impl Meta {
                     cmd: args.cmd() as c_ulong,
                     fd: args.fd(),
                 }),
+                // Anonymous mappings aren't backed by a file, so there's nothing to look up.
+                Syscall::Mmap(args) if args.flags() as c_int & MAP_ANONYMOUS == 0 => {
+                    Some(Typed::Mmap {
+                        fd: args.fd(),
+                        prot: args.prot() as c_int,
+                    })
+                }
                 Syscall::Execve(args) => Some(Typed::Exec {
                     path: unsafe { args.filename(pid) }?,
                     argv: unsafe { args.argv(pid) }.try_collect()?,
@@ -153,6 +165,8 @@ impl Meta {
         state: &mut process::State,
         sval: i64,
     ) -> Result<BTreeSet<Capability>, Error> {
+        use nix::libc::PROT_EXEC;
+
         let Self { nr, typed } = self;
 
         if let Some(typed) = typed {
@@ -200,6 +214,27 @@ impl Meta {
                         }
                     }
                 }
+                // Mapping a shared object as executable is how the dynamic loader loads it, but
+                // ld.so does the same for everything the program is linked against before it's
+                // entered, so only mappings made after that are dynamic loading.
+                Typed::Mmap { fd, prot } if prot & PROT_EXEC != 0 && state.has_entered() => {
+                    let meta = match state.get_fd(fd) {
+                        Some(meta) => Some(meta),
+                        None => match state.infer_fd(fd) {
+                            Ok(meta) => Some(meta),
+                            Err(e) => {
+                                tracing::warn!(?e, %fd, pid = %state.pid(), "inferring FD");
+                                None
+                            }
+                        },
+                    };
+                    if meta.is_some_and(|meta| meta.ty().is_shared_object()) {
+                        let mut caps = lookup_sysno(nr)?;
+                        caps.insert(Capability::DynamicLoading);
+                        return Ok(caps);
+                    }
+                }
+                Typed::Mmap { .. } => {}
             }
         }
 
@@ -1,82 +1,404 @@
-use std::{collections::BTreeSet, fmt::Debug, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
 
-use capslock::{Report, report};
-use llvm_ir_analysis::{ModuleAnalysis, llvm_ir::Module};
+use capslock::{Capability, CapabilityType, Report, report};
+use itertools::Itertools;
+use llvm_ir_analysis::{
+    CallContext, CallGraphOptions, CallResolution, ModuleSummary, SummaryCallGraph, llvm_ir::Module,
+};
+use rayon::prelude::*;
 
 use crate::{
-    caps::FunctionCaps, function::FunctionMap, graph::CallGraph, location::IntoOptionLocation,
+    caps::{FunctionCaps, Scope},
+    function::{FunctionMap, direct_fn_caps},
+    graph::{CallGraph, CallSite, Propagation},
+    location::IntoOptionLocation,
+    r#static::{
+        builtin,
+        cache::Cache,
+        cargo::{Artifacts, Kind},
+        entry::{entry_points, is_in_crate, public_api},
+        metadata::Dependencies,
+        module::ModuleInfo,
+    },
 };
 
 pub struct Bitcode {
     path: PathBuf,
     functions: FunctionMap,
     call_graph: CallGraph,
+    entry_points: BTreeSet<usize>,
+    api: BTreeSet<usize>,
+    packages: Vec<report::Package>,
+    propagation: Propagation,
 }
 
 pub struct Builder<'caps> {
-    bitcode: Bitcode,
+    path: PathBuf,
     function_caps: &'caps FunctionCaps,
+    artifacts: &'caps Artifacts,
+    api: bool,
+    call_graph_options: CallGraphOptions,
+    propagation: Propagation,
+    cache: Option<&'caps Cache>,
+    build: Option<report::BuildConfig>,
+    dependencies: Option<Dependencies>,
+    temporary_dir: Option<PathBuf>,
+
+    /// The modules that have been added, in the order of their paths.
+    modules: Vec<ParsedModule>,
 }
 
 impl<'caps> Builder<'caps> {
-    pub fn new(path: PathBuf, function_caps: &'caps FunctionCaps) -> Self {
+    pub fn new(
+        path: PathBuf,
+        function_caps: &'caps FunctionCaps,
+        artifacts: &'caps Artifacts,
+    ) -> Self {
         Self {
-            bitcode: Bitcode::new(path),
+            path,
             function_caps,
+            artifacts,
+            api: false,
+            call_graph_options: CallGraphOptions::default(),
+            propagation: Propagation::default(),
+            cache: None,
+            build: None,
+            dependencies: None,
+            temporary_dir: None,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Enables reporting the capabilities of each function in the public API of local library
+    /// crates.
+    pub fn with_api(self, api: bool) -> Self {
+        Self { api, ..self }
+    }
+
+    /// Sets which call graph edges transitive capabilities are propagated along.
+    pub fn with_propagation(self, propagation: Propagation) -> Self {
+        Self {
+            propagation,
+            ..self
+        }
+    }
+
+    /// Sets the options used to build the call graph.
+    pub fn with_call_graph_options(self, call_graph_options: CallGraphOptions) -> Self {
+        Self {
+            call_graph_options,
+            ..self
+        }
+    }
+
+    /// Records the Cargo options used to build the bitcode in the report's metadata.
+    pub fn with_build_config(self, build: report::BuildConfig) -> Self {
+        Self {
+            build: Some(build),
+            ..self
+        }
+    }
+
+    /// Restricts each process to the modules of the packages it can depend on.
+    ///
+    /// Without this, every module is assumed to be part of the program, and every library is
+    /// assumed to be callable from build scripts and proc-macros.
+    pub fn with_dependencies(self, dependencies: Dependencies) -> Self {
+        Self {
+            dependencies: Some(dependencies),
+            ..self
         }
     }
 
-    #[tracing::instrument(skip(self), err)]
-    pub fn add_module(&mut self, path: impl Into<PathBuf> + Debug) -> anyhow::Result<()> {
-        let path = path.into();
-        let module = Module::from_bc_path(&path).map_err(|s| anyhow::anyhow!("{s}"))?;
+    /// Records paths within the given target directory, which will be removed before the report
+    /// is read, relative to the workspace root as if they'd been built into Cargo's default
+    /// target directory.
+    pub fn with_temporary_dir(self, dir: &Path) -> Self {
+        Self {
+            temporary_dir: Some(dir.to_path_buf()),
+            ..self
+        }
+    }
+
+    /// Sets the cache used to avoid parsing bitcode files that haven't changed since a previous
+    /// run.
+    pub fn with_cache(self, cache: Option<&'caps Cache>) -> Self {
+        Self { cache, ..self }
+    }
 
-        // We need the function map for everything else to make sense.
-        self.upsert_function_map(&module)?;
+    /// Parses the given bitcode files.
+    ///
+    /// Modules are parsed in parallel, then kept in the order of their paths, so the report is the
+    /// same regardless of the order they're given in or which finishes parsing first.
+    #[tracing::instrument(skip_all, fields(modules = paths.len()), err)]
+    pub fn add_modules(&mut self, mut paths: Vec<PathBuf>) -> anyhow::Result<()> {
+        paths.sort();
 
-        // Get the call graph and adapt it for what we need to report later.
-        self.upsert_call_graph(&module)?;
+        let (function_caps, artifacts, api, cache) =
+            (self.function_caps, self.artifacts, self.api, self.cache);
+        let parsed = paths
+            .par_iter()
+            .map(|path| ParsedModule::parse(path, function_caps, artifacts, api, cache))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.modules.extend(parsed);
 
         Ok(())
     }
 
+    /// Analyses the modules that have been added.
+    ///
+    /// Build scripts and proc-macros run at compile time rather than as part of the program, so
+    /// each is analysed separately and reported as a child process, along with the libraries it
+    /// may call into.
     pub fn into_report(self) -> Report {
-        // TODO: gather package, module, and build metadata.
+        // TODO: gather module metadata.
+        let packages = self
+            .artifacts
+            .packages()
+            .iter()
+            .map(report::Package::from)
+            .collect::<Vec<_>>();
 
-        self.bitcode.into_report()
-    }
+        let role = |module: &ParsedModule| {
+            let (unit, package) = module.unit?;
+            let package = packages.get(package)?.clone();
+            match unit {
+                Kind::BuildScript => Some(report::Role::BuildScript { package }),
+                Kind::ProcMacro => Some(report::Role::ProcMacro { package }),
+                _ => None,
+            }
+        };
+        let package_id = |module: &ParsedModule| {
+            module
+                .unit
+                .and_then(|(_, package)| packages.get(package))
+                .map(|package| package.id.as_str())
+        };
+
+        // Modules that can't be attributed to a package are always included, since we can't tell
+        // whether they're needed.
+        let process = self.process(
+            self.report_path(&self.path),
+            self.modules
+                .iter()
+                .filter(|module| role(module).is_none())
+                .filter(|module| match (&self.dependencies, package_id(module)) {
+                    (Some(dependencies), Some(id)) => dependencies.is_runtime(id),
+                    _ => true,
+                })
+                .map(|module| (module, true)),
+            &packages,
+            self.api,
+        );
 
-    fn upsert_call_graph(&mut self, module: &Module) -> anyhow::Result<()> {
-        let analysis = ModuleAnalysis::new(module);
+        let children =
+            self.modules
+                .par_iter()
+                .filter_map(|root| Some((root, role(root)?)))
+                .map(|(root, role)| {
+                    let closure = self.dependencies.as_ref().zip(package_id(root)).map(
+                        |(dependencies, id)| match &role {
+                            report::Role::BuildScript { .. } => dependencies.build_script(id),
+                            report::Role::ProcMacro { .. } => dependencies.proc_macro(id),
+                        },
+                    );
 
-        let call_graph = analysis.call_graph();
-        let inner = call_graph.inner();
+                    // Only the compile-time module itself has entry points.
+                    let modules = self.modules.iter().filter_map(|module| {
+                        if std::ptr::eq(module, root) {
+                            Some((module, true))
+                        } else if module.is_lib()
+                            && closure
+                                .as_ref()
+                                .zip(package_id(module))
+                                .is_none_or(|(closure, id)| closure.contains(id))
+                        {
+                            Some((module, false))
+                        } else {
+                            None
+                        }
+                    });
 
-        for (caller, callee, call) in inner.all_edges() {
-            let caller = self.bitcode.functions.get_index(caller).unwrap();
-            let callee = self.bitcode.functions.get_index(callee).unwrap();
-            let location = call.debugloc().into_option_location();
+                    let mut process =
+                        self.process(self.report_path(&root.path), modules, &packages, false);
+                    process.role = Some(role);
+                    process
+                })
+                .collect();
 
-            self.bitcode.call_graph.add_edge(caller, callee, location);
+        Report {
+            process,
+            children,
+            metadata: self
+                .build
+                .map(|build| report::Metadata { build: Some(build) }),
         }
+    }
 
-        Ok(())
+    /// Returns the path to record in the report for the given artifact.
+    fn report_path(&self, path: &Path) -> PathBuf {
+        match self
+            .temporary_dir
+            .as_ref()
+            .and_then(|dir| path.strip_prefix(dir).ok())
+        {
+            Some(relative) => Path::new("target").join(relative),
+            None => path.to_path_buf(),
+        }
     }
 
-    fn upsert_function_map(&mut self, module: &Module) -> anyhow::Result<()> {
-        for func in module.functions.iter() {
-            self.bitcode
-                .functions
-                .upsert_with_caps(self.function_caps, func)?;
+    /// Merges the given modules, along with whether their entry points should be used, and
+    /// analyses the result as a single process.
+    fn process<'m>(
+        &self,
+        path: PathBuf,
+        modules: impl Iterator<Item = (&'m ParsedModule, bool)>,
+        packages: &[report::Package],
+        api: bool,
+    ) -> report::Process {
+        let mut bitcode = Bitcode::new(path);
+        bitcode.propagation = self.propagation;
+        bitcode.packages = packages.to_vec();
+
+        let mut summaries = Vec::new();
+        for (module, entry) in modules {
+            bitcode.merge(module, entry, api);
+            summaries.push(&module.summary);
         }
+        bitcode.upsert_call_graph(SummaryCallGraph::new(summaries, self.call_graph_options));
+
+        bitcode.into_process()
+    }
+}
+
+/// A single module that has been parsed and analysed independently of any other.
+struct ParsedModule {
+    path: PathBuf,
+    /// What's needed to build the call graph.
+    summary: ModuleSummary,
+    /// The kind of unit that produced the module and the index of its package, if known.
+    unit: Option<(Kind, usize)>,
+    /// Every function defined or declared in the module, by mangled name, along with whether it
+    /// was defined.
+    functions: Vec<(String, report::Function, bool)>,
+    entry_points: Vec<String>,
+    api: Vec<String>,
+}
 
-        for func in module.func_declarations.iter() {
-            self.bitcode
+impl ParsedModule {
+    #[tracing::instrument(skip(function_caps, artifacts, cache), err)]
+    fn parse(
+        path: &Path,
+        function_caps: &FunctionCaps,
+        artifacts: &Artifacts,
+        api: bool,
+        cache: Option<&Cache>,
+    ) -> anyhow::Result<Self> {
+        let info = || {
+            let module = if path.extension().is_some_and(|ext| ext == "ll") {
+                Module::from_ir_path(path)
+            } else {
+                Module::from_bc_path(path)
+            }
+            .map_err(|s| anyhow::anyhow!("{s}"))?;
+            ModuleInfo::new(&module)
+        };
+        let info = match cache {
+            Some(cache) => cache.get_or_insert_with(path, info)?,
+            None => info()?,
+        };
+
+        // Figure out which crate this module belongs to, so we can select the right versioned
+        // function capabilities.
+        let unit = artifacts.get(path).and_then(|unit| {
+            artifacts
+                .packages()
+                .get(unit.package)
+                .map(|package| (unit, package))
+        });
+        // Bitcode that wasn't built by Cargo was never going to be found.
+        if unit.is_none() && !artifacts.packages().is_empty() {
+            tracing::warn!(?path, "cannot find the package that produced this bitcode");
+        }
+        let mut scope = Scope::new(artifacts.versions());
+        if let Some((_, package)) = unit {
+            scope = scope.with_current(&package.name, &package.version);
+        }
+
+        let entry_points = entry_points(&info, unit).map(String::from).collect();
+
+        let mut api_functions = Vec::new();
+        if api
+            && let Some((crate_name, _hash)) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.rsplit_once('-'))
+        {
+            let display_names = info
                 .functions
-                .upsert_with_caps(self.function_caps, func)?;
+                .iter()
+                .map(|func| (func.mangled.as_str(), func.function.display_name()))
+                .collect::<HashMap<_, _>>();
+            api_functions = public_api(&info, unit)
+                .filter(|name| {
+                    display_names
+                        .get(name)
+                        .is_some_and(|display_name| is_in_crate(display_name, crate_name))
+                })
+                .map(String::from)
+                .collect();
         }
 
-        Ok(())
+        let ModuleInfo {
+            functions,
+            unsafe_pointer,
+            environ,
+            evidence,
+            summary,
+            ..
+        } = info;
+        let unsafe_pointer = unsafe_pointer.into_iter().collect::<HashSet<_>>();
+        let mut environ = environ.into_iter().into_group_map();
+        let mut evidence = evidence.into_iter().into_group_map();
+        let functions = functions
+            .into_iter()
+            .map(|func| {
+                let mut function = func.function;
+                function.capabilities = direct_fn_caps(function_caps, scope, &function.name);
+                let builtin = builtin::capabilities(function.display_name()).collect::<Vec<_>>();
+                for cap in builtin {
+                    function.insert_capability(cap, CapabilityType::Direct);
+                }
+                if unsafe_pointer.contains(&func.mangled) {
+                    function.insert_capability(Capability::UnsafePointer, CapabilityType::Direct);
+                }
+                for cap in environ.remove(&func.mangled).into_iter().flatten() {
+                    function.insert_capability(cap, CapabilityType::Direct);
+                }
+                function.evidence = evidence.remove(&func.mangled).unwrap_or_default();
+                (func.mangled, function, func.defined)
+            })
+            .collect();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            summary,
+            unit: unit.map(|(unit, _)| (unit.kind, unit.package)),
+            functions,
+            entry_points,
+            api: api_functions,
+        })
+    }
+}
+
+impl ParsedModule {
+    /// Returns true if the module is a library that compile-time code may also call into.
+    fn is_lib(&self) -> bool {
+        self.unit.is_some_and(|(kind, _)| kind == Kind::Lib)
     }
 }
 
@@ -86,41 +408,384 @@ impl Bitcode {
             path,
             functions: FunctionMap::default(),
             call_graph: CallGraph::default(),
+            entry_points: BTreeSet::new(),
+            api: BTreeSet::new(),
+            packages: Vec::new(),
+            propagation: Propagation::default(),
+        }
+    }
+
+    /// Adds the functions in the module, along with its entry points and API if requested.
+    fn merge(&mut self, module: &ParsedModule, entry: bool, api: bool) {
+        // Functions are attributed to the first package we see define them, even if they were
+        // previously seen as a declaration in another module. What we found in the body of each
+        // definition is merged in, since we may have seen the declaration first.
+        let package = module.unit.map(|(_, package)| package);
+        for (mangled, function, defined) in module.functions.iter() {
+            let idx = self.functions.upsert(mangled, function.clone());
+            if *defined && let Some(existing) = self.functions.get_mut(idx) {
+                if existing.package.is_none() {
+                    existing.package = package;
+                }
+                for (cap, ty) in function.capabilities.iter() {
+                    existing.insert_capability(*cap, *ty);
+                }
+                for evidence in function.evidence.iter() {
+                    if !existing.evidence.contains(evidence) {
+                        existing.evidence.push(evidence.clone());
+                    }
+                }
+            }
+        }
+
+        if !entry {
+            return;
+        }
+
+        // Record the entry points so we can figure out what's actually reachable later.
+        for name in module.entry_points.iter() {
+            if let Some(idx) = self.functions.get_index(name) {
+                self.entry_points.insert(idx);
+            }
+        }
+
+        if api {
+            for name in module.api.iter() {
+                if let Some(idx) = self.functions.get_index(name) {
+                    self.api.insert(idx);
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn upsert_call_graph(&mut self, call_graph: SummaryCallGraph) {
+        for (caller, callee, call) in call_graph.inner().all_edges() {
+            let caller = self.functions.get_index(caller).unwrap();
+            let callee = self.functions.get_index(callee).unwrap();
+            let kind = match call.resolution {
+                CallResolution::Direct => report::EdgeKind::Direct,
+                CallResolution::Vtable => report::EdgeKind::Vtable,
+                CallResolution::Indirect => report::EdgeKind::Indirect,
+            };
+            let context = match call.call.context {
+                CallContext::Panic => report::CallContext::Panic,
+                CallContext::Conditional => report::CallContext::Conditional,
+                CallContext::Unconditional => report::CallContext::Unconditional,
+            };
+
+            self.call_graph.add_edge(
+                caller,
+                callee,
+                CallSite {
+                    location: call.call.debugloc.as_ref().into_option_location(),
+                    kind: Some(kind),
+                    invoke: call.call.is_invoke,
+                    context: Some(context),
+                },
+            );
         }
     }
 
     #[tracing::instrument(skip(self))]
-    pub fn into_report(self) -> Report {
+    pub fn into_process(self) -> report::Process {
         let Self {
             path,
             mut functions,
             call_graph,
+            entry_points,
+            api,
+            packages,
+            propagation,
         } = self;
 
         // Bubble the direct capabilities up as transitive capabilities via the call graph.
-        call_graph.bubble_transitive_capabilities(&mut functions);
+        call_graph.bubble_transitive_capabilities(&mut functions, propagation);
+
+        // Only functions reachable from an entry point contribute to the overall capabilities.
+        let reachable = if entry_points.is_empty() {
+            tracing::warn!("no entry points found; treating every function as reachable");
+            None
+        } else {
+            Some(call_graph.reachable(entry_points.iter().copied()))
+        };
 
         let functions = functions.into_functions();
 
-        let capabilities = functions.iter().fold(BTreeSet::new(), |mut acc, func| {
-            acc.extend(func.capabilities.keys().copied());
-            acc
-        });
+        let mut capabilities = BTreeSet::new();
+        let mut unreachable = Vec::new();
+        for (idx, func) in functions.iter().enumerate() {
+            if reachable
+                .as_ref()
+                .is_none_or(|reachable| reachable.contains(&idx))
+            {
+                capabilities.extend(func.capabilities.keys().copied());
+            } else if func.capabilities.keys().any(|cap| cap != &Capability::Safe) {
+                unreachable.push(idx);
+            }
+        }
 
-        Report {
-            process: report::Process {
-                path,
-                capabilities,
-                functions,
-                edges: call_graph.into(),
-            },
-            children: Vec::new(),
+        let mut process = report::Process {
+            path,
+            role: None,
+            capabilities,
+            functions,
+            edges: call_graph.into(),
+            packages,
+            unreachable,
+            api: Vec::new(),
+        };
+
+        // Spell out the path behind each reachable function's transitive capabilities, so readers
+        // don't have to follow the witnesses through the report themselves.
+        let unreachable = process.unreachable.iter().copied().collect::<HashSet<_>>();
+        let paths = (0..process.functions.len())
+            .map(|idx| {
+                if unreachable.contains(&idx) {
+                    BTreeMap::new()
+                } else {
+                    witness_paths(&process, idx)
+                }
+            })
+            .collect::<Vec<_>>();
+        for (func, paths) in process.functions.iter_mut().zip(paths) {
+            func.paths = paths;
         }
+
+        // The transitive capabilities have already been bubbled up, so each API item's
+        // capabilities are simply those of its function.
+        process.api = api
+            .into_iter()
+            .filter_map(|idx| {
+                let func = process.functions.get(idx)?;
+                Some(report::ApiItem {
+                    function: idx,
+                    capabilities: func
+                        .capabilities
+                        .keys()
+                        .copied()
+                        .filter(|cap| cap != &Capability::Safe)
+                        .collect(),
+                    paths: witness_paths(&process, idx),
+                })
+            })
+            .collect();
+
+        process
     }
 }
 
+/// Returns the call path from the function to a function with each of its capabilities directly,
+/// leaving out those that it has directly itself.
+fn witness_paths(
+    process: &report::Process,
+    function: usize,
+) -> BTreeMap<Capability, Vec<report::Witness>> {
+    let Some(func) = process.functions.get(function) else {
+        return BTreeMap::new();
+    };
+
+    func.capabilities
+        .keys()
+        .filter(|cap| *cap != &Capability::Safe)
+        .map(|cap| (*cap, process.path(function, *cap)))
+        .filter(|(_, path)| !path.is_empty())
+        .map(|(cap, path)| (cap, path.into_iter().cloned().collect()))
+        .collect()
+}
+
 impl Debug for Bitcode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Bitcode").field("path", &self.path).finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#static::cargo::{Artifact, Profile, Target};
+
+    fn module(package: usize, function: report::Function, defined: bool) -> ParsedModule {
+        ParsedModule {
+            path: PathBuf::from(format!("{package}.bc")),
+            summary: ModuleSummary::default(),
+            unit: Some((Kind::Lib, package)),
+            functions: vec![(function.display_name().to_string(), function, defined)],
+            entry_points: Vec::new(),
+            api: Vec::new(),
+        }
+    }
+
+    /// A module of the given kind that defines a single function, which is its entry point.
+    fn unit(kind: Kind, package: usize, name: &str) -> ParsedModule {
+        ParsedModule {
+            path: PathBuf::from(format!("{name}.bc")),
+            unit: Some((kind, package)),
+            entry_points: vec![name.to_string()],
+            ..module(package, function(name), true)
+        }
+    }
+
+    fn function(name: &str) -> report::Function {
+        report::Function {
+            name: report::FunctionName::Other {
+                display_name: name.to_string(),
+                language: "C".to_string(),
+            },
+            location: None,
+            capabilities: BTreeMap::new(),
+            syscalls: BTreeSet::new(),
+            package: None,
+            witnesses: BTreeMap::new(),
+            paths: BTreeMap::new(),
+            evidence: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn merge_declaration_first() {
+        let declared = module(0, function("getenv_wrapper"), false);
+
+        let mut function = function("getenv_wrapper");
+        function.insert_capability(Capability::UnsafePointer, CapabilityType::Direct);
+        function.insert_capability(Capability::ReadSystemState, CapabilityType::Direct);
+        function.evidence.push(report::Evidence {
+            callee: "getenv".to_string(),
+            argument: "HOME".to_string(),
+            location: None,
+        });
+        let defined = module(1, function, true);
+
+        // What's found in the body of the definition survives the declaration being seen first,
+        // and the function is attributed to the package that defined it.
+        let mut bitcode = Bitcode::new(PathBuf::from("test"));
+        bitcode.merge(&declared, true, false);
+        bitcode.merge(&defined, true, false);
+
+        let idx = bitcode.functions.get_index("getenv_wrapper").unwrap();
+        let merged = bitcode.functions.get(idx).unwrap();
+        assert_eq!(
+            merged.capabilities,
+            BTreeMap::from([
+                (Capability::UnsafePointer, CapabilityType::Direct),
+                (Capability::ReadSystemState, CapabilityType::Direct),
+            ])
+        );
+        assert_eq!(merged.evidence.len(), 1);
+        assert_eq!(merged.package, Some(1));
+    }
+
+    #[test]
+    fn compile_time_children() {
+        let mut artifacts = Artifacts::default();
+        for (id, name, kind) in [
+            ("path+file:///home/user/app#0.1.0", "app", "bin"),
+            (
+                "path+file:///home/user/app#0.1.0",
+                "build-script-build",
+                "custom-build",
+            ),
+            (
+                "registry+https://github.com/rust-lang/crates.io-index#derive@1.0.0",
+                "derive",
+                "proc-macro",
+            ),
+            (
+                "registry+https://github.com/rust-lang/crates.io-index#dep@1.0.0",
+                "dep",
+                "lib",
+            ),
+        ] {
+            artifacts.insert(Artifact {
+                package_id: id.into(),
+                target: Target {
+                    name: name.into(),
+                    kind: vec![kind.into()],
+                },
+                profile: Profile { test: false },
+                filenames: Vec::new(),
+                executable: None,
+            });
+        }
+
+        let function_caps = FunctionCaps::default();
+        let mut builder = Builder::new(PathBuf::from("app"), &function_caps, &artifacts);
+        builder.modules = vec![
+            unit(Kind::Bin, 0, "main"),
+            unit(Kind::BuildScript, 0, "build_script_main"),
+            unit(Kind::ProcMacro, 1, "derive"),
+            unit(Kind::Lib, 2, "dep_fn"),
+        ];
+        let report = builder.into_report();
+
+        let functions = |process: &report::Process| {
+            process
+                .functions
+                .iter()
+                .map(|func| func.display_name().to_string())
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        // Compile-time code is kept out of the program, and each piece of it gets the libraries
+        // it may call into.
+        assert!(report.process.role.is_none());
+        assert_eq!(functions(&report.process), vec!["dep_fn", "main"]);
+
+        let children = report
+            .children
+            .iter()
+            .map(|child| (child.role.as_ref().unwrap().to_string(), functions(child)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            children,
+            vec![
+                (
+                    "build script of app".to_string(),
+                    vec!["build_script_main".to_string(), "dep_fn".to_string()]
+                ),
+                (
+                    "proc-macro derive".to_string(),
+                    vec!["dep_fn".to_string(), "derive".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reachable_paths() {
+        let mut bitcode = Bitcode::new(PathBuf::from("app"));
+        let [main, run, spawn, orphan] = ["main", "run", "spawn", "orphan"]
+            .map(|name| bitcode.functions.upsert(name, function(name)));
+        bitcode
+            .functions
+            .get_mut(spawn)
+            .unwrap()
+            .insert_capability(Capability::Exec, CapabilityType::Direct);
+
+        // `orphan` has the capability too, but nothing calls it.
+        bitcode.call_graph.add_edge(main, run, CallSite::default());
+        bitcode.call_graph.add_edge(run, spawn, CallSite::default());
+        bitcode
+            .call_graph
+            .add_edge(orphan, spawn, CallSite::default());
+        bitcode.entry_points.insert(main);
+
+        let process = bitcode.into_process();
+        let path = |idx: usize| {
+            process.functions[idx]
+                .paths
+                .get(&Capability::Exec)
+                .map(|path| {
+                    path.iter()
+                        .map(|witness| witness.callee)
+                        .collect::<Vec<_>>()
+                })
+        };
+        assert_eq!(path(main), Some(vec![run, spawn]));
+        assert_eq!(path(run), Some(vec![spawn]));
+        assert_eq!(path(spawn), None);
+        assert_eq!(process.unreachable, vec![orphan]);
+        assert_eq!(path(orphan), None);
+    }
+}
@@ -0,0 +1,93 @@
+use capslock::Capability;
+
+use crate::r#static::entry::is_function;
+
+/// Functions that directly have a capability regardless of the function capability mapping, since
+/// they're the primitives everything else that has it is built on.
+const BUILTIN: &[(&str, Capability)] = &[
+    // The dynamic loader, on Unix and Windows.
+    ("dlopen", Capability::DynamicLoading),
+    ("dlmopen", Capability::DynamicLoading),
+    ("dlsym", Capability::DynamicLoading),
+    ("dlvsym", Capability::DynamicLoading),
+    ("LoadLibraryA", Capability::DynamicLoading),
+    ("LoadLibraryW", Capability::DynamicLoading),
+    ("LoadLibraryExA", Capability::DynamicLoading),
+    ("LoadLibraryExW", Capability::DynamicLoading),
+    ("GetProcAddress", Capability::DynamicLoading),
+    // libloading, which is how most Rust code does the above.
+    ("libloading::safe::Library::new", Capability::DynamicLoading),
+    (
+        "libloading::os::unix::Library::new",
+        Capability::DynamicLoading,
+    ),
+    (
+        "libloading::os::unix::Library::open",
+        Capability::DynamicLoading,
+    ),
+    (
+        "libloading::os::windows::Library::new",
+        Capability::DynamicLoading,
+    ),
+    (
+        "libloading::os::windows::Library::load_with_flags",
+        Capability::DynamicLoading,
+    ),
+    // The environment, which is read and written without any system calls.
+    ("getenv", Capability::ReadSystemState),
+    ("secure_getenv", Capability::ReadSystemState),
+    ("setenv", Capability::ModifySystemState),
+    ("unsetenv", Capability::ModifySystemState),
+    ("putenv", Capability::ModifySystemState),
+    ("clearenv", Capability::ModifySystemState),
+    ("std::env::var", Capability::ReadSystemState),
+    ("std::env::var_os", Capability::ReadSystemState),
+    ("std::env::vars", Capability::ReadSystemState),
+    ("std::env::vars_os", Capability::ReadSystemState),
+    ("std::env::set_var", Capability::ModifySystemState),
+    ("std::env::remove_var", Capability::ModifySystemState),
+];
+
+/// Returns the capabilities that the function with the given display name always has.
+pub fn capabilities(display_name: &str) -> impl Iterator<Item = Capability> + '_ {
+    BUILTIN
+        .iter()
+        .filter(move |(path, _)| is_function(display_name, path))
+        .map(|(_, cap)| *cap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment() {
+        assert_eq!(
+            capabilities("std::env::var::<&str>").collect::<Vec<_>>(),
+            vec![Capability::ReadSystemState]
+        );
+        assert_eq!(
+            capabilities("setenv").collect::<Vec<_>>(),
+            vec![Capability::ModifySystemState]
+        );
+        assert_eq!(capabilities("std::env::args").count(), 0);
+    }
+
+    #[test]
+    fn dynamic_loading() {
+        for name in [
+            "dlopen",
+            "libloading::os::unix::Library::open::<&str>",
+            "libloading::safe::Library::new<&std::path::Path>",
+        ] {
+            assert_eq!(
+                capabilities(name).collect::<Vec<_>>(),
+                vec![Capability::DynamicLoading],
+                "{name}"
+            );
+        }
+
+        assert_eq!(capabilities("libloading::Library::get").count(), 0);
+        assert_eq!(capabilities("dlerror").count(), 0);
+    }
+}
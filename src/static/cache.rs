@@ -0,0 +1,137 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Bumped whenever anything that's cached changes shape or meaning, in addition to the package
+/// version, so that development builds don't pick up stale entries.
+const CACHE_VERSION: u32 = 7;
+
+/// A persistent cache of per-module analysis results, keyed by the content of each bitcode file
+/// and the version of the analyser that produced them.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Opens the cache in the given directory, creating it if necessary.
+    pub fn new(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(dir.join("modules"))?;
+
+        Ok(Self { dir })
+    }
+
+    /// Returns the default cache directory for the current user, if there is one.
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("cargo-capslock"))
+    }
+
+    /// Returns a target directory that persists between runs for the given workspace, so that
+    /// Cargo only rebuilds what's changed.
+    pub fn target_dir(&self, workspace: &Path) -> anyhow::Result<PathBuf> {
+        let workspace = workspace.canonicalize()?;
+        let hash = blake3::hash(workspace.as_os_str().as_encoded_bytes());
+        let dir = self.dir.join("target").join(&hash.to_hex()[..16]);
+        fs::create_dir_all(&dir)?;
+
+        Ok(dir)
+    }
+
+    /// Returns the cached value for the given bitcode file, or computes and caches it if there
+    /// isn't one.
+    ///
+    /// Failing to read or write the cache isn't fatal: the value is computed as if the cache
+    /// didn't exist.
+    #[tracing::instrument(skip(self, compute), err)]
+    pub fn get_or_insert_with<T>(
+        &self,
+        bitcode: &Path,
+        compute: impl FnOnce() -> anyhow::Result<T>,
+    ) -> anyhow::Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let path = self.entry_path(bitcode)?;
+
+        match File::open(&path) {
+            Ok(file) => match serde_json::from_reader(BufReader::new(file)) {
+                Ok(value) => {
+                    tracing::debug!(?path, "cache hit");
+                    return Ok(value);
+                }
+                Err(e) => tracing::warn!(?path, %e, "ignoring unreadable cache entry"),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!(?path, %e, "cannot open cache entry"),
+        }
+
+        let value = compute()?;
+        if let Err(e) = Self::write(&path, &value) {
+            tracing::warn!(?path, %e, "cannot write cache entry");
+        }
+
+        Ok(value)
+    }
+
+    fn entry_path(&self, bitcode: &Path) -> anyhow::Result<PathBuf> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update(&CACHE_VERSION.to_le_bytes());
+        hasher.update(&fs::read(bitcode)?);
+
+        Ok(self
+            .dir
+            .join("modules")
+            .join(hasher.finalize().to_hex().as_str())
+            .with_extension("json"))
+    }
+
+    fn write(path: &Path, value: &impl Serialize) -> anyhow::Result<()> {
+        // Write to a temporary file first, so that concurrent runs never see a partial entry.
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        {
+            let mut writer = BufWriter::new(file.as_file_mut());
+            serde_json::to_writer(&mut writer, value)?;
+            writer.flush()?;
+        }
+        file.persist(path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = Cache::new(dir.path().join("cache"))?;
+
+        let bitcode = dir.path().join("a.bc");
+        fs::write(&bitcode, b"one")?;
+
+        let mut computed = 0;
+        let mut get = |cache: &Cache| {
+            cache.get_or_insert_with(&bitcode, || {
+                computed += 1;
+                Ok(vec![computed])
+            })
+        };
+        assert_eq!(get(&cache)?, vec![1]);
+        assert_eq!(get(&cache)?, vec![1]);
+
+        // Changing the content invalidates the entry.
+        fs::write(&bitcode, b"two")?;
+        assert_eq!(get(&cache)?, vec![2]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,583 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::Metadata,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+};
+
+use capslock::report;
+use semver::Version;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::caps::CrateVersions;
+
+/// The subset of Cargo's JSON messages that we care about.
+///
+/// We use our own types rather than escargot's, since escargot doesn't expose the package ID.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum Message {
+    CompilerArtifact(Artifact),
+    CompilerMessage(CompilerMessage),
+    BuildFinished(BuildFinished),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompilerMessage {
+    pub message: Diagnostic,
+}
+
+/// A diagnostic emitted by rustc.
+#[derive(Debug, Deserialize)]
+pub struct Diagnostic {
+    /// The severity, such as `error` or `warning`.
+    pub level: String,
+    /// The diagnostic as rustc would have printed it, if available.
+    pub rendered: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BuildFinished {
+    pub success: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum BuildError {
+    #[error("cargo build failed with {errors} compiler error(s)")]
+    Compile { errors: usize },
+
+    #[error("cargo failed: {0}")]
+    Cargo(ExitStatus),
+
+    #[error("cannot parse {count} message(s) from cargo, so the build's artifacts are unknown")]
+    Messages { count: usize },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Artifact {
+    pub package_id: String,
+    pub target: Target,
+    pub profile: Profile,
+    pub filenames: Vec<PathBuf>,
+    pub executable: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    pub test: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub kind: Vec<String>,
+}
+
+/// A package that contributed at least one artifact to the build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub version: Version,
+}
+
+/// The kind of target that a bitcode file was built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Lib,
+    ProcMacro,
+    Bin,
+    Test,
+    BuildScript,
+    Other,
+}
+
+impl Kind {
+    fn new(target: &Target, profile: &Profile) -> Self {
+        let is = |kind: &str| target.kind.iter().any(|known| known == kind);
+
+        if profile.test {
+            Self::Test
+        } else if is("custom-build") {
+            Self::BuildScript
+        } else if is("proc-macro") {
+            Self::ProcMacro
+        } else if is("bin") {
+            Self::Bin
+        } else if ["lib", "rlib", "dylib", "cdylib", "staticlib"]
+            .into_iter()
+            .any(is)
+        {
+            Self::Lib
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// A single compilation unit within the build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unit {
+    /// The index of the package within [`Artifacts::packages`].
+    pub package: usize,
+    pub kind: Kind,
+}
+
+impl From<&Package> for report::Package {
+    fn from(package: &Package) -> Self {
+        Self {
+            id: package.id.clone(),
+            name: package.name.clone(),
+            version: package.version.to_string(),
+        }
+    }
+}
+
+impl Package {
+    /// Parses a Cargo package ID.
+    ///
+    /// Modern versions of Cargo use the package ID specification format (for example,
+    /// `registry+https://github.com/rust-lang/crates.io-index#serde@1.0.228`), which omits the
+    /// name if it matches the last path segment of the source URL. Older versions use
+    /// `serde 1.0.228 (registry+https://github.com/rust-lang/crates.io-index)`.
+    pub fn from_id(id: &str) -> Option<Self> {
+        let (name, version) = if let Some((source, fragment)) = id.rsplit_once('#') {
+            match fragment.split_once('@') {
+                Some((name, version)) => (name, version),
+                None => (
+                    source
+                        .trim_end_matches('/')
+                        .rsplit('/')
+                        .next()
+                        .filter(|name| !name.is_empty())?,
+                    fragment,
+                ),
+            }
+        } else {
+            let mut fields = id.split_ascii_whitespace();
+            (fields.next()?, fields.next()?)
+        };
+
+        Some(Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            version: Version::parse(version).ok()?,
+        })
+    }
+
+    /// Returns true if the package is local to the filesystem, which generally means it's part of
+    /// the workspace being analysed rather than a dependency.
+    pub fn is_local(&self) -> bool {
+        self.id.starts_with("path+") || self.id.contains("(path+")
+    }
+}
+
+/// Maps the bitcode files emitted by rustc back to the packages that produced them.
+#[derive(Debug, Default)]
+pub struct Artifacts {
+    packages: Vec<Package>,
+
+    /// Units keyed by the file stem rustc uses for artifacts in `deps`, which is the crate name
+    /// followed by the metadata hash: for example, `serde-0123456789abcdef`.
+    stems: HashMap<String, Unit>,
+
+    /// Units keyed by crate name, for the cases where Cargo only reports uplifted artifacts
+    /// without the metadata hash.
+    crate_names: HashMap<String, Vec<Unit>>,
+
+    /// Executables built, excluding build scripts.
+    executables: Vec<PathBuf>,
+
+    versions: CrateVersions,
+}
+
+impl Artifacts {
+    pub fn insert(&mut self, artifact: Artifact) {
+        let Some(package) = Package::from_id(&artifact.package_id) else {
+            tracing::warn!(id = %artifact.package_id, "cannot parse package ID");
+            return;
+        };
+
+        let idx = match self.packages.iter().position(|known| known == &package) {
+            Some(idx) => idx,
+            None => {
+                self.versions.insert(&package.name, package.version.clone());
+                self.packages.push(package);
+                self.packages.len() - 1
+            }
+        };
+
+        let unit = Unit {
+            package: idx,
+            kind: Kind::new(&artifact.target, &artifact.profile),
+        };
+
+        let crate_name = artifact.target.name.replace('-', "_");
+        let prefix = format!("{crate_name}-");
+        let mut has_stem = false;
+        for stem in artifact
+            .filenames
+            .iter()
+            .filter_map(|filename| filename.file_stem()?.to_str())
+        {
+            // Library artifacts have a `lib` prefix that the bitcode won't.
+            let stem = stem
+                .strip_prefix("lib")
+                .filter(|stem| stem.starts_with(&prefix))
+                .unwrap_or(stem);
+            if stem.starts_with(&prefix) {
+                self.stems.insert(stem.to_string(), unit);
+                has_stem = true;
+            }
+        }
+
+        // Binaries are only reported by their uplifted paths, without the metadata hash, but
+        // they're links to or copies of the files rustc wrote with it.
+        if !has_stem {
+            for stem in artifact
+                .filenames
+                .iter()
+                .filter_map(|filename| original_stem(filename, &prefix))
+            {
+                self.stems.insert(stem, unit);
+                has_stem = true;
+            }
+        }
+
+        if !has_stem {
+            self.crate_names.entry(crate_name).or_default().push(unit);
+        }
+
+        if let Some(executable) = artifact.executable
+            && unit.kind != Kind::BuildScript
+        {
+            self.executables.push(executable);
+        }
+    }
+
+    /// Finds the unit that produced the given bitcode file.
+    pub fn get(&self, bitcode: &Path) -> Option<Unit> {
+        let stem = bitcode.file_stem()?.to_str()?;
+        if let Some(unit) = self.stems.get(stem) {
+            return Some(*unit);
+        }
+
+        // Fall back to the crate name, provided that it's unambiguous.
+        let (crate_name, _hash) = stem.rsplit_once('-')?;
+        match self.crate_names.get(crate_name)?.as_slice() {
+            [unit] => Some(*unit),
+            _ => None,
+        }
+    }
+
+    /// Filters the given bitcode files down to those produced by this build, dropping any left
+    /// over from previous builds in the same target directory.
+    ///
+    /// Bitcode files are matched to units by their metadata hash. Where Cargo only reported
+    /// artifacts for a crate name that we couldn't find the hash for, bitcode files with that name
+    /// are only kept if there's exactly one for each unit, since there's no telling which are
+    /// current otherwise.
+    pub fn current(&self, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut current = Vec::new();
+        let mut by_crate_name: HashMap<&str, Vec<PathBuf>> = HashMap::new();
+
+        for path in paths {
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if self.stems.contains_key(stem) {
+                current.push(path);
+            } else if let Some((crate_name, _hash)) = stem.rsplit_once('-')
+                && let Some((crate_name, _)) = self.crate_names.get_key_value(crate_name)
+            {
+                by_crate_name.entry(crate_name).or_default().push(path);
+            }
+        }
+
+        for (crate_name, paths) in by_crate_name {
+            if paths.len() == self.crate_names[crate_name].len() {
+                current.extend(paths);
+            } else {
+                tracing::warn!(
+                    crate_name,
+                    ?paths,
+                    "cannot tell which bitcode is from the current build, so none of it will be \
+                     analysed"
+                );
+            }
+        }
+
+        current
+    }
+
+    /// Returns the units that none of the given bitcode files were produced by.
+    pub fn without_bitcode(&self, paths: &[PathBuf]) -> Vec<Unit> {
+        let stems = paths
+            .iter()
+            .filter_map(|path| path.file_stem()?.to_str())
+            .collect::<HashSet<_>>();
+
+        let mut missing = self
+            .stems
+            .iter()
+            .filter(|(stem, _)| !stems.contains(stem.as_str()))
+            .map(|(_, unit)| *unit)
+            .collect::<Vec<_>>();
+        missing.extend(
+            self.crate_names
+                .iter()
+                .filter(|(crate_name, _)| {
+                    !stems.iter().any(|stem| {
+                        stem.rsplit_once('-')
+                            .is_some_and(|(name, _hash)| name == crate_name.as_str())
+                    })
+                })
+                .flat_map(|(_, units)| units.iter().copied()),
+        );
+
+        missing
+    }
+
+    /// Returns the executable that was built, provided exactly one was.
+    pub fn executable(&self) -> Option<&Path> {
+        match self.executables.as_slice() {
+            [executable] => Some(executable),
+            _ => None,
+        }
+    }
+
+    pub fn packages(&self) -> &[Package] {
+        &self.packages
+    }
+
+    pub fn versions(&self) -> &CrateVersions {
+        &self.versions
+    }
+}
+
+/// Finds the file that rustc wrote with the metadata hash in its name, which Cargo uplifted to the
+/// given path by linking or copying it, and returns its stem.
+fn original_stem(uplifted: &Path, prefix: &str) -> Option<String> {
+    let metadata = uplifted.metadata().ok()?;
+    let dir = uplifted.parent()?;
+
+    // Binaries are written to `deps`, while examples are written alongside their uplifted copies.
+    [dir.join("deps"), dir.to_path_buf()]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path != uplifted
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(prefix))
+        })
+        .find(|path| is_same_file(uplifted, &metadata, path))
+        .and_then(|path| Some(path.file_stem()?.to_str()?.to_string()))
+}
+
+fn is_same_file(path: &Path, metadata: &Metadata, other: &Path) -> bool {
+    let Ok(other_metadata) = other.metadata() else {
+        return false;
+    };
+    if (metadata.dev(), metadata.ino()) == (other_metadata.dev(), other_metadata.ino()) {
+        return true;
+    }
+
+    // Cargo copies the file if it can't link it.
+    other_metadata.is_file()
+        && metadata.len() == other_metadata.len()
+        && std::fs::read(path)
+            .ok()
+            .zip(std::fs::read(other).ok())
+            .is_some_and(|(contents, other_contents)| contents == other_contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_id() {
+        use insta::assert_compact_debug_snapshot as snapshot;
+
+        snapshot!(Package::from_id("registry+https://github.com/rust-lang/crates.io-index#serde@1.0.228"), @r#"Some(Package { id: "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.228", name: "serde", version: Version { major: 1, minor: 0, patch: 228 } })"#);
+        snapshot!(Package::from_id("path+file:///home/user/foo#0.1.0"), @r#"Some(Package { id: "path+file:///home/user/foo#0.1.0", name: "foo", version: Version { major: 0, minor: 1, patch: 0 } })"#);
+        snapshot!(Package::from_id("serde 1.0.228 (registry+https://github.com/rust-lang/crates.io-index)"), @r#"Some(Package { id: "serde 1.0.228 (registry+https://github.com/rust-lang/crates.io-index)", name: "serde", version: Version { major: 1, minor: 0, patch: 228 } })"#);
+        snapshot!(Package::from_id("garbage"), @"None");
+    }
+
+    #[test]
+    fn bitcode_lookup() {
+        let mut artifacts = Artifacts::default();
+        artifacts.insert(Artifact {
+            package_id: "registry+https://github.com/rust-lang/crates.io-index#libc@0.2.177".into(),
+            target: Target {
+                name: "libc".into(),
+                kind: vec!["lib".into()],
+            },
+            profile: Profile { test: false },
+            filenames: vec![
+                "/t/debug/deps/liblibc-0123456789abcdef.rlib".into(),
+                "/t/debug/deps/liblibc-0123456789abcdef.rmeta".into(),
+            ],
+            executable: None,
+        });
+        artifacts.insert(Artifact {
+            package_id: "path+file:///home/user/my-bin#0.1.0".into(),
+            target: Target {
+                name: "my-bin".into(),
+                kind: vec!["bin".into()],
+            },
+            profile: Profile { test: false },
+            filenames: vec!["/t/debug/my-bin".into()],
+            executable: Some("/t/debug/my-bin".into()),
+        });
+
+        let unit = |path: &str| {
+            artifacts
+                .get(Path::new(path))
+                .map(|unit| (artifacts.packages()[unit.package].name.as_str(), unit.kind))
+        };
+        assert_eq!(
+            unit("/t/debug/deps/libc-0123456789abcdef.bc"),
+            Some(("libc", Kind::Lib))
+        );
+        assert_eq!(
+            unit("/t/debug/deps/my_bin-fedcba9876543210.bc"),
+            Some(("my-bin", Kind::Bin))
+        );
+        assert_eq!(unit("/t/debug/deps/other-fedcba9876543210.bc"), None);
+
+        assert_eq!(artifacts.executable(), Some(Path::new("/t/debug/my-bin")));
+    }
+
+    #[test]
+    fn current_bitcode() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let profile = dir.path();
+        let deps = profile.join("deps");
+        let examples = profile.join("examples");
+        std::fs::create_dir_all(&deps)?;
+        std::fs::create_dir_all(&examples)?;
+        let file = |path: PathBuf, contents: &str| -> anyhow::Result<PathBuf> {
+            std::fs::write(&path, contents)?;
+            Ok(path)
+        };
+
+        // The current build of the binary, followed by a build of it with another configuration,
+        // which leaves the other configuration's bitcode as the newest.
+        file(deps.join("my_bin-3333333333333333"), "current")?;
+        let current_bin = file(deps.join("my_bin-3333333333333333.bc"), "")?;
+        file(deps.join("my_bin-2222222222222222"), "other")?;
+        let other_bin = file(deps.join("my_bin-2222222222222222.bc"), "")?;
+        std::fs::hard_link(deps.join("my_bin-3333333333333333"), profile.join("my-bin"))?;
+
+        // Examples are uplifted within the same directory, and copied if they can't be linked.
+        file(examples.join("demo-5555555555555555"), "current")?;
+        let current_example = file(examples.join("demo-5555555555555555.bc"), "")?;
+        file(examples.join("demo-6666666666666666"), "other!!")?;
+        let other_example = file(examples.join("demo-6666666666666666.bc"), "")?;
+        file(examples.join("demo"), "current")?;
+
+        let mut artifacts = Artifacts::default();
+        artifacts.insert(Artifact {
+            package_id: "registry+https://github.com/rust-lang/crates.io-index#libc@0.2.177".into(),
+            target: Target {
+                name: "libc".into(),
+                kind: vec!["lib".into()],
+            },
+            profile: Profile { test: false },
+            filenames: vec![deps.join("liblibc-0123456789abcdef.rlib")],
+            executable: None,
+        });
+        artifacts.insert(Artifact {
+            package_id: "path+file:///home/user/my-bin#0.1.0".into(),
+            target: Target {
+                name: "my-bin".into(),
+                kind: vec!["bin".into()],
+            },
+            profile: Profile { test: false },
+            filenames: vec![profile.join("my-bin")],
+            executable: Some(profile.join("my-bin")),
+        });
+        artifacts.insert(Artifact {
+            package_id: "path+file:///home/user/my-bin#0.1.0".into(),
+            target: Target {
+                name: "demo".into(),
+                kind: vec!["example".into()],
+            },
+            profile: Profile { test: false },
+            filenames: vec![examples.join("demo")],
+            executable: Some(examples.join("demo")),
+        });
+
+        let paths = vec![
+            file(deps.join("libc-0123456789abcdef.bc"), "")?,
+            file(deps.join("libc-1111111111111111.bc"), "")?,
+            current_bin.clone(),
+            other_bin,
+            current_example.clone(),
+            other_example,
+            file(deps.join("other-4444444444444444.bc"), "")?,
+        ];
+
+        let mut current = artifacts.current(paths);
+        current.sort();
+        assert_eq!(
+            current,
+            vec![
+                deps.join("libc-0123456789abcdef.bc"),
+                current_bin,
+                current_example,
+            ]
+        );
+
+        assert_eq!(artifacts.without_bitcode(&current), Vec::new());
+        let mut missing = artifacts
+            .without_bitcode(&[])
+            .into_iter()
+            .map(|unit| unit.kind)
+            .collect::<Vec<_>>();
+        missing.sort_by_key(|kind| format!("{kind:?}"));
+        assert_eq!(missing, vec![Kind::Bin, Kind::Lib, Kind::Other]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn messages() -> anyhow::Result<()> {
+        let message = serde_json::from_str::<Message>(
+            r#"{"reason":"compiler-message","package_id":"path+file:///home/user/foo#0.1.0","manifest_path":"/home/user/foo/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"foo","src_path":"/home/user/foo/src/lib.rs","edition":"2024","doc":true,"doctest":true,"test":true},"message":{"rendered":"error[E0425]: cannot find value `x` in this scope\n","$message_type":"diagnostic","children":[],"code":{"code":"E0425","explanation":null},"level":"error","message":"cannot find value `x` in this scope","spans":[]}}"#,
+        )?;
+        let Message::CompilerMessage(message) = message else {
+            panic!("unexpected message: {message:?}");
+        };
+        assert_eq!(message.message.level, "error");
+        assert_eq!(
+            message.message.rendered.as_deref(),
+            Some("error[E0425]: cannot find value `x` in this scope\n")
+        );
+
+        let message =
+            serde_json::from_str::<Message>(r#"{"reason":"build-finished","success":false}"#)?;
+        assert!(matches!(
+            message,
+            Message::BuildFinished(BuildFinished { success: false })
+        ));
+
+        let message = serde_json::from_str::<Message>(
+            r#"{"reason":"build-script-executed","package_id":"path+file:///home/user/foo#0.1.0"}"#,
+        )?;
+        assert!(matches!(message, Message::Other));
+
+        Ok(())
+    }
+}
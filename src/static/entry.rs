@@ -0,0 +1,120 @@
+use crate::r#static::{
+    cargo::{Kind, Package, Unit},
+    module::ModuleInfo,
+};
+
+/// Returns the names of the functions in the module that are treated as entry points.
+///
+/// Entry points are:
+///
+/// * `main`, which covers binaries, build scripts, and the test harness's own entry point;
+/// * unmangled functions with external linkage, which covers `#[no_mangle]` and `extern "C"`
+///   exports;
+/// * functions with external linkage in local library crates, which approximates their public
+///   API; and
+/// * functions referenced from global initializers in local test crates, which is how the test
+///   harness finds each test; and
+/// * functions referenced from global initializers in proc-macro crates, which is how the compiler
+///   finds each macro.
+pub fn entry_points<'m>(
+    module: &'m ModuleInfo,
+    unit: Option<(Unit, &Package)>,
+) -> impl Iterator<Item = &'m str> + 'm {
+    let kind = unit.map(|(unit, _)| unit.kind);
+    let local_kind = unit
+        .filter(|(_, package)| package.is_local())
+        .map(|(unit, _)| unit.kind);
+
+    let defined = module
+        .functions
+        .iter()
+        .filter(|func| func.defined)
+        .filter(move |func| {
+            func.mangled == "main"
+                || (func.external && (!is_mangled(&func.mangled) || local_kind == Some(Kind::Lib)))
+        })
+        .map(|func| func.mangled.as_str());
+
+    let tests = module
+        .referenced
+        .iter()
+        .filter(move |_| local_kind == Some(Kind::Test) || kind == Some(Kind::ProcMacro))
+        .map(String::as_str);
+
+    defined.chain(tests)
+}
+
+/// Returns the names of the functions in the module that may make up the public API of a local
+/// library crate.
+///
+/// There's no notion of visibility in the bitcode, so this is every function with external
+/// linkage. Callers should further filter the result with [`is_in_crate`] once the names are
+/// demangled, since generic instantiations from other crates can also be exported.
+pub fn public_api<'m>(
+    module: &'m ModuleInfo,
+    unit: Option<(Unit, &Package)>,
+) -> impl Iterator<Item = &'m str> + 'm {
+    let is_local_lib =
+        unit.is_some_and(|(unit, package)| unit.kind == Kind::Lib && package.is_local());
+
+    module
+        .functions
+        .iter()
+        .filter(move |func| is_local_lib && func.defined && func.external)
+        .map(|func| func.mangled.as_str())
+}
+
+/// Returns true if the demangled function name belongs to the given crate: either a function or
+/// inherent method within it, or a trait implementation on one of its types.
+pub fn is_in_crate(display_name: &str, crate_name: &str) -> bool {
+    display_name
+        .trim_start_matches('<')
+        .strip_prefix(crate_name)
+        .is_some_and(|rest| rest.starts_with("::"))
+}
+
+/// Returns true if the demangled function name is the given function, or an instantiation of it.
+pub fn is_function(display_name: &str, path: &str) -> bool {
+    display_name
+        .strip_prefix(path)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::<") || rest.starts_with('<'))
+}
+
+fn is_mangled(name: &str) -> bool {
+    // Both the legacy and v0 Rust mangling schemes, along with C++.
+    name.starts_with("_Z") || name.starts_with("_R")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_crate() {
+        assert!(is_in_crate("mycrate::Client::send", "mycrate"));
+        assert!(is_in_crate("mycrate::connect", "mycrate"));
+        assert!(is_in_crate(
+            "<mycrate::Client as core::fmt::Debug>::fmt",
+            "mycrate"
+        ));
+
+        assert!(!is_in_crate("mycrate_macros::derive", "mycrate"));
+        assert!(!is_in_crate(
+            "core::ptr::drop_in_place<mycrate::Client>",
+            "mycrate"
+        ));
+        assert!(!is_in_crate("mycrate", "mycrate"));
+    }
+
+    #[test]
+    fn function() {
+        assert!(is_function("core::ptr::read", "core::ptr::read"));
+        assert!(is_function("core::ptr::read::<u8>", "core::ptr::read"));
+        assert!(is_function("core::ptr::read<u8>", "core::ptr::read"));
+        assert!(is_function("dlopen", "dlopen"));
+
+        assert!(!is_function("core::ptr::read_unaligned", "core::ptr::read"));
+        assert!(!is_function("my::core::ptr::read", "core::ptr::read"));
+        assert!(!is_function("dlopen_wrapper", "dlopen"));
+    }
+}
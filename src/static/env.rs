@@ -0,0 +1,88 @@
+use std::collections::BTreeSet;
+
+use capslock::Capability;
+use llvm_ir_analysis::llvm_ir::{Constant, Function, Instruction, Module, Name, Operand};
+
+/// The names libc exports the process environment under.
+const ENVIRON: &[&str] = &["environ", "__environ", "_environ"];
+
+/// Returns the defined functions in the module that access the process environment directly
+/// through `environ`, rather than through a function such as `getenv`, along with the capabilities
+/// that implies.
+///
+/// Storing to `environ` replaces the environment, and is treated as modifying system state; any
+/// other use is treated as reading it.
+pub fn environ_functions(module: &Module) -> impl Iterator<Item = (&str, Capability)> {
+    module.functions.iter().flat_map(|func| {
+        environ_access(func)
+            .into_iter()
+            .map(|cap| (func.name.as_str(), cap))
+    })
+}
+
+fn environ_access(func: &Function) -> BTreeSet<Capability> {
+    let mut caps = BTreeSet::new();
+    for inst in func.basic_blocks.iter().flat_map(|bb| bb.instrs.iter()) {
+        match inst {
+            Instruction::Store(store) if is_environ(&store.address) => {
+                caps.insert(Capability::ModifySystemState);
+            }
+            Instruction::Store(store) if is_environ(&store.value) => {
+                caps.insert(Capability::ReadSystemState);
+            }
+            Instruction::Load(load) if is_environ(&load.address) => {
+                caps.insert(Capability::ReadSystemState);
+            }
+            Instruction::Call(call) if call.arguments.iter().any(|(arg, _)| is_environ(arg)) => {
+                caps.insert(Capability::ReadSystemState);
+            }
+            _ => {}
+        }
+    }
+
+    caps
+}
+
+/// Returns true if the operand is a constant pointer into `environ`.
+fn is_environ(operand: &Operand) -> bool {
+    match operand {
+        Operand::ConstantOperand(constant) => is_environ_constant(constant),
+        _ => false,
+    }
+}
+
+fn is_environ_constant(constant: &Constant) -> bool {
+    match constant {
+        Constant::GlobalReference {
+            name: Name::Name(name),
+            ..
+        } => ENVIRON.contains(&name.as_str()),
+        Constant::BitCast(cast) => is_environ_constant(&cast.operand),
+        Constant::AddrSpaceCast(cast) => is_environ_constant(&cast.operand),
+        Constant::GetElementPtr(gep) => is_environ_constant(&gep.address),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environ() {
+        let module = Module::from_bc_path("tests/bcfiles/env.bc").unwrap();
+        let mut functions = environ_functions(&module).collect::<Vec<_>>();
+        functions.sort();
+
+        // `calls_first_var` doesn't touch `environ` itself, so it only picks up
+        // `ReadSystemState` transitively once capabilities are propagated over the call graph.
+        assert_eq!(
+            functions,
+            vec![
+                ("clear_env", Capability::ModifySystemState),
+                ("count_vars", Capability::ReadSystemState),
+                ("first_var", Capability::ReadSystemState),
+            ]
+        );
+    }
+}
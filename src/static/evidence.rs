@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use capslock::report;
+use llvm_ir_analysis::llvm_ir::{
+    Constant, ConstantRef, Instruction, Module, Name, Operand, Terminator, instruction::Call,
+    terminator::Invoke,
+};
+
+use crate::{
+    location::IntoOptionLocation,
+    r#static::{entry::is_function, pointer::callee_name},
+};
+
+/// Functions whose constant string arguments say something about the capability they use.
+const SINKS: &[&str] = &[
+    "std::process::Command::new",
+    "std::process::Command::arg",
+    "std::fs::File::open",
+    "std::fs::File::create",
+    "std::fs::read",
+    "std::fs::read_to_string",
+    "std::fs::write",
+    "std::net::TcpStream::connect",
+    "std::net::TcpListener::bind",
+    "std::net::UdpSocket::bind",
+    "std::env::var",
+    "std::env::var_os",
+    "std::env::set_var",
+    "std::env::remove_var",
+    "getenv",
+    "setenv",
+    "dlopen",
+    "libloading::safe::Library::new",
+];
+
+/// Returns the constant string arguments passed to known sinks by each defined function in the
+/// module, by the mangled name of the caller.
+///
+/// `display_names` maps each function's mangled name to its display name.
+pub fn constant_arguments(
+    module: &Module,
+    display_names: &HashMap<&str, &str>,
+) -> Vec<(String, report::Evidence)> {
+    let strings = Strings::new(module);
+
+    let mut evidence = Vec::new();
+    for func in module.functions.iter() {
+        for bb in func.basic_blocks.iter() {
+            let invoke = match &bb.term {
+                Terminator::Invoke(Invoke {
+                    function,
+                    arguments,
+                    debugloc,
+                    ..
+                }) => Some((function, arguments, debugloc)),
+                _ => None,
+            };
+            let calls = bb
+                .instrs
+                .iter()
+                .filter_map(|inst| match inst {
+                    Instruction::Call(Call {
+                        function,
+                        arguments,
+                        debugloc,
+                        ..
+                    }) => Some((function, arguments, debugloc)),
+                    _ => None,
+                })
+                .chain(invoke);
+
+            for (callee, arguments, debugloc) in calls {
+                let Some(callee) = callee
+                    .as_ref()
+                    .right()
+                    .and_then(callee_name)
+                    .and_then(|name| display_names.get(name))
+                else {
+                    continue;
+                };
+                if !SINKS.iter().any(|sink| is_function(callee, sink)) {
+                    continue;
+                }
+
+                let arguments = arguments.iter().map(|(arg, _)| arg).collect::<Vec<_>>();
+                for (i, arg) in arguments.iter().enumerate() {
+                    // Rust passes a `&str` as a pointer followed by its length.
+                    let len = arguments.get(i + 1).and_then(|next| constant_int(next));
+                    if let Some(argument) = strings.get(arg, len) {
+                        evidence.push((
+                            func.name.clone(),
+                            report::Evidence {
+                                callee: callee.to_string(),
+                                argument,
+                                location: debugloc.into_option_location(),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    evidence
+}
+
+/// The constant byte strings in a module, by the name of the global that holds them.
+struct Strings<'m> {
+    globals: HashMap<&'m str, Vec<u8>>,
+}
+
+impl<'m> Strings<'m> {
+    fn new(module: &'m Module) -> Self {
+        let globals = module
+            .global_vars
+            .iter()
+            .filter(|var| var.is_constant)
+            .filter_map(|var| {
+                let Name::Name(name) = &var.name else {
+                    return None;
+                };
+                Some((name.as_str(), bytes(var.initializer.as_ref()?)?))
+            })
+            .collect();
+
+        Self { globals }
+    }
+
+    /// Returns the string that the operand points to, if it's a constant pointer to the start of
+    /// a constant string.
+    ///
+    /// Strings containing a NUL are taken to be C strings; otherwise, the string is truncated to
+    /// the given length if there is one.
+    fn get(&self, operand: &Operand, len: Option<u64>) -> Option<String> {
+        let Operand::ConstantOperand(constant) = operand else {
+            return None;
+        };
+        let bytes = self.globals.get(global_name(constant)?)?;
+        let bytes = match (bytes.iter().position(|b| *b == 0), len) {
+            (Some(nul), _) => &bytes[..nul],
+            (None, Some(len)) => bytes.get(..usize::try_from(len).ok()?)?,
+            (None, None) => bytes,
+        };
+        if bytes.is_empty() {
+            return None;
+        }
+
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+/// Returns the bytes of a constant array of `i8`, possibly wrapped in a packed struct as rustc
+/// emits them.
+fn bytes(constant: &ConstantRef) -> Option<Vec<u8>> {
+    match constant.as_ref() {
+        Constant::Array { elements, .. } => elements
+            .iter()
+            .map(|element| match element.as_ref() {
+                Constant::Int { bits: 8, value } => Some(*value as u8),
+                _ => None,
+            })
+            .collect(),
+        Constant::Struct { values, .. } if values.len() == 1 => bytes(&values[0]),
+        _ => None,
+    }
+}
+
+/// Returns the name of the global that a constant pointer points to the start of.
+fn global_name(constant: &Constant) -> Option<&str> {
+    match constant {
+        Constant::GlobalReference {
+            name: Name::Name(name),
+            ..
+        } => Some(name.as_str()),
+        Constant::BitCast(cast) => global_name(&cast.operand),
+        Constant::GetElementPtr(gep)
+            if gep
+                .indices
+                .iter()
+                .all(|index| matches!(index.as_ref(), Constant::Int { value: 0, .. })) =>
+        {
+            global_name(&gep.address)
+        }
+        _ => None,
+    }
+}
+
+fn constant_int(operand: &Operand) -> Option<u64> {
+    match operand {
+        Operand::ConstantOperand(constant) => match constant.as_ref() {
+            Constant::Int { value, .. } => Some(*value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    #[test]
+    fn constant_strings() {
+        let module = Module::from_bc_path("tests/bcfiles/evidence.bc").unwrap();
+        let display_names = HashMap::from([
+            ("command_new", "std::process::Command::new::<&str>"),
+            ("getenv", "getenv"),
+            ("log", "app::log"),
+        ]);
+
+        let evidence = constant_arguments(&module, &display_names)
+            .into_iter()
+            .map(|(caller, evidence)| (caller, evidence.callee, evidence.argument))
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            evidence,
+            vec![
+                ("c_string", "getenv", "HOME"),
+                ("str_pair", "std::process::Command::new::<&str>", "git"),
+            ]
+            .into_iter()
+            .map(|(caller, callee, argument)| {
+                (caller.to_string(), callee.to_string(), argument.to_string())
+            })
+            .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use capslock::{Capability, report};
+
+use crate::r#static::metadata::Metadata;
+
+/// The name of the wrapper package's dependency on the package whose features are being built,
+/// which is renamed so that it can't clash with the wrapper itself.
+const WRAPPED: &str = "wrapped";
+
+/// A package and the Cargo features it declares.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PackageFeatures {
+    pub package: report::Package,
+    /// The package's features, including `default` if declared.
+    pub features: Vec<String>,
+    /// Whether the package is a member of the workspace, since Cargo only allows the features of
+    /// workspace members to be selected.
+    pub member: bool,
+    manifest_path: PathBuf,
+}
+
+impl PackageFeatures {
+    /// Finds the features of the package selected with `--package`, or the root package if there
+    /// isn't one.
+    pub fn new(metadata: &Metadata, spec: Option<&str>) -> anyhow::Result<Self> {
+        let package = match spec {
+            Some(spec) => metadata.find(spec)?,
+            None => metadata.root()?,
+        };
+
+        Ok(Self {
+            package: report::Package {
+                id: package.id.clone(),
+                name: package.name.clone(),
+                version: package.version.clone(),
+            },
+            features: package.features.keys().cloned().collect(),
+            member: metadata.workspace_members.contains(&package.id),
+            manifest_path: package.manifest_path.clone(),
+        })
+    }
+
+    /// Writes a package to the given directory that depends on this package without its default
+    /// features, and has a feature of the same name forwarding to each of its features.
+    ///
+    /// This allows the features of a package outside the workspace to be selected one at a time.
+    /// The workspace's lockfile is copied over, if there is one, so that the same versions of
+    /// everything else are used.
+    pub fn write_wrapper(&self, dir: &Path, workspace_root: &Path) -> anyhow::Result<()> {
+        let package_dir = self
+            .manifest_path
+            .parent()
+            .with_context(|| format!("cannot find the source of {}", self.spec()))?;
+
+        let mut manifest = format!(
+            r#"[package]
+name = "cargo-capslock-feature-matrix"
+version = "0.0.0"
+edition = "2021"
+publish = false
+
+[lib]
+path = "lib.rs"
+
+[dependencies.{WRAPPED}]
+package = {name}
+path = {path}
+default-features = false
+
+[features]
+"#,
+            name = toml_string(&self.package.name)?,
+            path = toml_string(&package_dir.to_string_lossy())?,
+        );
+        for feature in self.features.iter() {
+            writeln!(
+                manifest,
+                "{} = [{}]",
+                toml_string(feature)?,
+                toml_string(&format!("{WRAPPED}/{feature}"))?
+            )?;
+        }
+
+        // An empty workspace, so that the wrapper isn't taken to be part of any workspace it
+        // happens to be within.
+        manifest.push_str("\n[workspace]\n");
+
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join("Cargo.toml"), manifest)?;
+        std::fs::write(dir.join("lib.rs"), "")?;
+
+        let lockfile = workspace_root.join("Cargo.lock");
+        if lockfile.exists() {
+            std::fs::copy(lockfile, dir.join("Cargo.lock"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a package spec that selects exactly this package.
+    pub fn spec(&self) -> String {
+        format!("{}@{}", self.package.name, self.package.version)
+    }
+}
+
+/// Quotes a string for TOML, whose basic strings accept every escape that JSON strings use.
+fn toml_string(s: &str) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(s)?)
+}
+
+/// Builds a feature matrix from the capabilities found with no features enabled, and with each
+/// feature enabled on its own.
+pub fn matrix(
+    package: report::Package,
+    baseline: BTreeSet<Capability>,
+    features: impl IntoIterator<Item = (String, BTreeSet<Capability>)>,
+) -> report::FeatureMatrix {
+    let features = features
+        .into_iter()
+        .map(|(feature, caps)| (feature, caps.difference(&baseline).copied().collect()))
+        .collect::<BTreeMap<_, _>>();
+
+    report::FeatureMatrix {
+        package,
+        baseline,
+        features,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Metadata {
+        serde_json::from_value(serde_json::json!({
+            "packages": [
+                {
+                    "id": "path+file:///home/user/app#0.1.0",
+                    "name": "app",
+                    "version": "0.1.0",
+                    "features": {},
+                },
+                {
+                    "id": "registry+https://github.com/rust-lang/crates.io-index#tokio@1.48.0",
+                    "name": "tokio",
+                    "version": "1.48.0",
+                    "features": {"default": [], "fs": [], "net": ["libc"]},
+                },
+            ],
+            "workspace_members": ["path+file:///home/user/app#0.1.0"],
+            "resolve": {"root": "path+file:///home/user/app#0.1.0"},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn package_features() {
+        let tokio = PackageFeatures::new(&metadata(), Some("tokio")).unwrap();
+        assert_eq!(tokio.package.name, "tokio");
+        assert_eq!(tokio.features, vec!["default", "fs", "net"]);
+        assert_eq!(tokio.spec(), "tokio@1.48.0");
+
+        let root = PackageFeatures::new(&metadata(), None).unwrap();
+        assert_eq!(root.package.name, "app");
+        assert_eq!(root.features, Vec::<String>::new());
+    }
+
+    #[test]
+    fn dependency_wrapper() -> anyhow::Result<()> {
+        let toolchain = std::env::var("RUSTUP_TOOLCHAIN").unwrap_or_else(|_| "stable".into());
+        let dir = tempfile::tempdir()?;
+        let write = |path: &str, contents: &str| -> anyhow::Result<()> {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, contents)?;
+            Ok(())
+        };
+
+        // A workspace with a dependency outside of it, whose features Cargo won't let us select
+        // directly.
+        write(
+            "dep/Cargo.toml",
+            "[package]\nname = \"dep\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+             [features]\ndefault = [\"fs\"]\nfs = []\nnet = []\n",
+        )?;
+        write("dep/src/lib.rs", "")?;
+        write(
+            "app/Cargo.toml",
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+             [dependencies]\ndep = { path = \"../dep\" }\n\n[workspace]\n",
+        )?;
+        write("app/src/main.rs", "fn main() {}\n")?;
+
+        let metadata = Metadata::load(&dir.path().join("app"), &toolchain, ["--offline"])?;
+        let package = PackageFeatures::new(&metadata, Some("dep"))?;
+        assert!(!package.member);
+        assert_eq!(package.features, vec!["default", "fs", "net"]);
+
+        // Each feature can be selected through the wrapper instead, without the defaults.
+        let wrapper = dir.path().join("wrapper");
+        package.write_wrapper(&wrapper, &metadata.workspace_root)?;
+        assert!(wrapper.join("Cargo.lock").exists());
+        for feature in package.features.iter() {
+            let metadata = Metadata::load(
+                &wrapper,
+                &toolchain,
+                ["--offline", "--no-default-features", "--features", feature],
+            )?;
+            assert_eq!(metadata.find("dep")?.id, package.package.id);
+            assert_eq!(metadata.root()?.name, "cargo-capslock-feature-matrix");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn added_capabilities() {
+        let package = PackageFeatures::new(&metadata(), Some("tokio"))
+            .unwrap()
+            .package;
+        let matrix = matrix(
+            package,
+            BTreeSet::from([Capability::Safe, Capability::ReadSystemState]),
+            [
+                (
+                    "fs".to_string(),
+                    BTreeSet::from([Capability::Safe, Capability::Files]),
+                ),
+                (
+                    "net".to_string(),
+                    BTreeSet::from([
+                        Capability::Safe,
+                        Capability::ReadSystemState,
+                        Capability::Network,
+                    ]),
+                ),
+            ],
+        );
+
+        assert_eq!(
+            matrix.features,
+            BTreeMap::from([
+                ("fs".to_string(), BTreeSet::from([Capability::Files])),
+                ("net".to_string(), BTreeSet::from([Capability::Network])),
+            ])
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+
+/// The subset of `cargo metadata` output that we care about.
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    pub packages: Vec<Package>,
+    pub workspace_members: Vec<String>,
+    /// Only reported by Cargo 1.71 and later.
+    #[serde(default)]
+    pub workspace_default_members: Vec<String>,
+    #[serde(default)]
+    pub workspace_root: PathBuf,
+    pub resolve: Option<Resolve>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub targets: Vec<Target>,
+    #[serde(default)]
+    pub manifest_path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Target {
+    /// The kinds of the target, such as `lib`, `bin` or `proc-macro`.
+    pub kind: Vec<String>,
+}
+
+impl Package {
+    /// Returns true if the package is a proc-macro, which only ever runs within the compiler.
+    pub fn is_proc_macro(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target.kind.iter().any(|kind| kind == "proc-macro"))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Resolve {
+    pub root: Option<String>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    id: String,
+    #[serde(default)]
+    deps: Vec<NodeDep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeDep {
+    pkg: String,
+    #[serde(default)]
+    dep_kinds: Vec<DepKindInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DepKindInfo {
+    /// `None` for normal dependencies, otherwise `dev` or `build`.
+    kind: Option<String>,
+}
+
+impl Metadata {
+    /// Runs `cargo metadata` in the given workspace with the given extra arguments.
+    #[tracing::instrument(skip(args), err)]
+    pub fn load(
+        workspace: &Path,
+        toolchain: &str,
+        args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> anyhow::Result<Self> {
+        let output = Command::new("cargo")
+            .args(["metadata", "--format-version", "1"])
+            .args(args)
+            .env("RUSTUP_TOOLCHAIN", toolchain)
+            .current_dir(workspace)
+            .output()
+            .context("cannot run cargo metadata")?;
+        if !output.status.success() {
+            bail!(
+                "cargo metadata failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    /// Finds the package matching a package spec as given to `--package`: a name, optionally
+    /// followed by `@` and a version or version prefix.
+    pub fn find(&self, spec: &str) -> anyhow::Result<&Package> {
+        let (name, version) = spec
+            .split_once('@')
+            .map_or((spec, None), |(name, version)| (name, Some(version)));
+        let mut matches = self.packages.iter().filter(|package| {
+            package.name == name
+                && version.is_none_or(|version| package.version.starts_with(version))
+        });
+
+        match (matches.next(), matches.next()) {
+            (Some(package), None) => Ok(package),
+            (None, _) => bail!("cannot find package {spec}"),
+            (Some(_), Some(_)) => {
+                bail!("package {spec} is ambiguous; specify a version as in {name}@x.y.z")
+            }
+        }
+    }
+
+    /// Returns the root package of the workspace, if it isn't virtual.
+    pub fn root(&self) -> anyhow::Result<&Package> {
+        let root = self
+            .resolve
+            .as_ref()
+            .and_then(|resolve| resolve.root.as_ref())
+            .context("the workspace has no root package; use --package to select one")?;
+
+        self.packages
+            .iter()
+            .find(|package| &package.id == root)
+            .with_context(|| format!("cannot find root package {root}"))
+    }
+
+    /// Returns the IDs of the packages Cargo builds when no package is selected.
+    pub fn default_members(&self) -> Vec<String> {
+        if !self.workspace_default_members.is_empty() {
+            return self.workspace_default_members.clone();
+        }
+
+        match self
+            .resolve
+            .as_ref()
+            .and_then(|resolve| resolve.root.as_ref())
+        {
+            Some(root) => vec![root.clone()],
+            None => self.workspace_members.clone(),
+        }
+    }
+}
+
+/// The resolved package dependency graph, which determines which libraries each analysed target
+/// can actually call into.
+#[derive(Debug, Default)]
+pub struct Dependencies {
+    /// The dependencies of each package, by package ID.
+    deps: HashMap<String, Vec<Dependency>>,
+
+    /// The packages that the selected packages depend on at run time, including themselves.
+    runtime: HashSet<String>,
+
+    /// The packages that are proc-macros.
+    proc_macros: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct Dependency {
+    package: String,
+    normal: bool,
+    dev: bool,
+    build: bool,
+}
+
+impl Dependencies {
+    /// Resolves the dependencies of the given packages, including their dev-dependencies if
+    /// requested, as when building tests or examples.
+    pub fn new(metadata: &Metadata, roots: &[String], dev: bool) -> Self {
+        let mut deps = HashMap::new();
+        for node in metadata
+            .resolve
+            .iter()
+            .flat_map(|resolve| resolve.nodes.iter())
+        {
+            let node_deps = node
+                .deps
+                .iter()
+                .map(|dep| {
+                    let mut dependency = Dependency {
+                        package: dep.pkg.clone(),
+                        ..Default::default()
+                    };
+                    for kind in dep.dep_kinds.iter() {
+                        match kind.kind.as_deref() {
+                            None => dependency.normal = true,
+                            Some("dev") => dependency.dev = true,
+                            Some("build") => dependency.build = true,
+                            Some(_) => {}
+                        }
+                    }
+                    dependency
+                })
+                .collect();
+            deps.insert(node.id.clone(), node_deps);
+        }
+
+        let mut dependencies = Self {
+            deps,
+            runtime: HashSet::new(),
+            proc_macros: metadata
+                .packages
+                .iter()
+                .filter(|package| package.is_proc_macro())
+                .map(|package| package.id.clone())
+                .collect(),
+        };
+
+        let mut start = roots.to_vec();
+        if dev {
+            start.extend(
+                roots
+                    .iter()
+                    .flat_map(|root| dependencies.direct(root, |dep| dep.dev)),
+            );
+        }
+        dependencies.runtime = dependencies.closure(start);
+
+        dependencies
+    }
+
+    /// Returns true if the selected packages depend on the given package at run time.
+    pub fn is_runtime(&self, package: &str) -> bool {
+        self.runtime.contains(package)
+    }
+
+    /// Returns the packages that the build script of the given package can depend on.
+    pub fn build_script(&self, package: &str) -> HashSet<String> {
+        self.closure(self.direct(package, |dep| {
+            dep.build && !self.proc_macros.contains(&dep.package)
+        }))
+    }
+
+    /// Returns the packages that the given proc-macro package depends on, including itself.
+    pub fn proc_macro(&self, package: &str) -> HashSet<String> {
+        self.closure([package.to_string()])
+    }
+
+    fn direct(&self, package: &str, kind: impl Fn(&Dependency) -> bool) -> Vec<String> {
+        self.deps
+            .get(package)
+            .into_iter()
+            .flatten()
+            .filter(|dep| kind(dep))
+            .map(|dep| dep.package.clone())
+            .collect()
+    }
+
+    /// Returns the given packages and everything they depend on through normal dependencies.
+    ///
+    /// Proc-macros that are depended on are excluded, along with anything only they depend on,
+    /// since they run within the compiler rather than as part of the dependent code.
+    fn closure(&self, start: impl IntoIterator<Item = String>) -> HashSet<String> {
+        let mut seen = HashSet::new();
+        let mut queue = start.into_iter().collect::<Vec<_>>();
+        while let Some(package) = queue.pop() {
+            if seen.insert(package.clone()) {
+                queue.extend(self.direct(&package, |dep| {
+                    dep.normal && !self.proc_macros.contains(&dep.package)
+                }));
+            }
+        }
+
+        seen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Metadata {
+        let node = |id: &str, deps: &[(&str, Option<&str>)]| {
+            serde_json::json!({
+                "id": id,
+                "deps": deps
+                    .iter()
+                    .map(|(pkg, kind)| serde_json::json!({
+                        "pkg": pkg,
+                        "dep_kinds": [{"kind": kind, "target": null}],
+                    }))
+                    .collect::<Vec<_>>(),
+            })
+        };
+
+        serde_json::from_value(serde_json::json!({
+            "packages": [
+                {"id": "app", "name": "app", "version": "0.1.0", "features": {}},
+                {"id": "serde", "name": "serde", "version": "1.0.228", "features": {}},
+                {
+                    "id": "derive",
+                    "name": "serde_derive",
+                    "version": "1.0.228",
+                    "features": {},
+                    "targets": [{"kind": ["proc-macro"]}],
+                },
+                {"id": "syn", "name": "syn", "version": "2.0.111", "features": {}},
+                {"id": "cc", "name": "cc", "version": "1.2.0", "features": {}},
+                {"id": "libc", "name": "libc", "version": "0.2.177", "features": {}},
+                {"id": "insta", "name": "insta", "version": "1.44.1", "features": {}},
+                {"id": "rand-0.8", "name": "rand", "version": "0.8.5", "features": {}},
+                {"id": "rand-0.9", "name": "rand", "version": "0.9.2", "features": {}},
+            ],
+            "workspace_members": ["app"],
+            "resolve": {
+                "root": "app",
+                "nodes": [
+                    node("app", &[
+                        ("serde", None),
+                        ("cc", Some("build")),
+                        ("insta", Some("dev")),
+                    ]),
+                    node("serde", &[("derive", None)]),
+                    node("derive", &[("syn", None)]),
+                    node("cc", &[("libc", None)]),
+                ],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn find() {
+        let metadata = metadata();
+        assert_eq!(metadata.find("serde").unwrap().id, "serde");
+        assert_eq!(metadata.find("rand@0.9").unwrap().id, "rand-0.9");
+        assert!(metadata.find("rand").is_err());
+        assert!(metadata.find("tokio").is_err());
+
+        assert_eq!(metadata.root().unwrap().id, "app");
+        assert_eq!(metadata.default_members(), vec!["app"]);
+    }
+
+    #[test]
+    fn dependencies() {
+        let metadata = metadata();
+        let set = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<HashSet<_>>();
+
+        // The derive macro and its dependencies only run within the compiler.
+        let deps = Dependencies::new(&metadata, &["app".to_string()], false);
+        for id in ["app", "serde"] {
+            assert!(deps.is_runtime(id), "{id}");
+        }
+        for id in ["derive", "syn", "cc", "libc", "insta"] {
+            assert!(!deps.is_runtime(id), "{id}");
+        }
+
+        let deps = Dependencies::new(&metadata, &["app".to_string()], true);
+        assert!(deps.is_runtime("insta"));
+
+        assert_eq!(deps.build_script("app"), set(&["cc", "libc"]));
+        assert_eq!(deps.build_script("serde"), set(&[]));
+        assert_eq!(deps.proc_macro("derive"), set(&["derive", "syn"]));
+    }
+}
@@ -1,44 +1,174 @@
 use std::{
     borrow::Cow,
+    collections::HashSet,
     ffi::OsString,
+    io::{BufRead, BufReader},
     os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
+    process::Stdio,
 };
 
-use clap::Parser;
-use escargot::{CargoBuild, CommandMessages};
+use anyhow::Context;
+use capslock::{Report, report};
+use clap::{ArgGroup, Parser, ValueEnum};
+use escargot::CargoBuild;
 use itertools::Itertools;
+use llvm_ir_analysis::CallGraphOptions;
 use tempfile::TempDir;
 use walkdir::WalkDir;
 
-use crate::caps::FunctionCaps;
+use crate::{caps::FunctionCaps, graph::Propagation};
 
 mod bitcode;
+mod builtin;
+mod cache;
+mod cargo;
+mod entry;
+mod env;
+mod evidence;
+mod features;
+mod metadata;
+mod module;
+mod pointer;
+mod table;
+mod toolchain;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
+#[command(group = ArgGroup::new("cargo").multiple(true).args([
+    "all_features",
+    "bin",
+    "examples",
+    "feature_matrix",
+    "features",
+    "lib",
+    "no_default_features",
+    "package",
+    "profile",
+    "release",
+    "target",
+    "tests",
+    "workspace",
+]))]
 pub struct Static {
+    /// Only link indirect calls to functions whose address is taken.
+    ///
+    /// By default, a call through a function pointer is assumed to be able to reach any function
+    /// with a matching type, which is conservative but can attribute capabilities to callers
+    /// that can never actually reach them.
+    #[arg(long)]
+    address_taken_only: bool,
+
+    /// Activate all available features of all selected packages.
+    #[arg(long)]
+    all_features: bool,
+
+    /// Report the capabilities of each public function in the library crates being built.
+    ///
+    /// This is useful for libraries without a `main`, where the interesting question is what
+    /// calling a particular function can do, rather than what the crate as a whole can do.
+    #[arg(long)]
+    api: bool,
+
     /// Build only the specified binary.
     #[arg(long)]
     bin: Option<OsString>,
 
+    /// Directory to cache build artifacts and analysis results in between runs.
+    ///
+    /// Defaults to `cargo-capslock` within the user's cache directory.
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Build all examples.
+    #[arg(long)]
+    examples: bool,
+
+    /// Build the package once with no features, and then once with each of its features enabled
+    /// on its own, and report the capabilities that each feature adds, including those of build
+    /// scripts and proc-macros.
+    ///
+    /// The package is the one selected with `--package`, which may be a dependency, or the root
+    /// package of the workspace otherwise. Dependencies are built through a generated package
+    /// that depends on them, since Cargo only allows the features of workspace members to be
+    /// selected.
+    #[arg(long, conflicts_with_all = ["all_features", "api", "features", "no_default_features"])]
+    feature_matrix: bool,
+
+    /// Space or comma separated list of features to activate.
+    #[arg(short = 'F', long, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Output format.
+    ///
+    /// `json` outputs the full Capslock report, while `table` outputs a summary of the
+    /// capabilities used by each package, or by each public function if `--api` is given.
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Analyse the given LLVM bitcode (`.bc`) or textual IR (`.ll`) files instead of building
+    /// the workspace.
+    ///
+    /// Directories are searched recursively for such files. This allows bitcode from builds not
+    /// driven by Cargo to be analysed, but since there's no Cargo metadata, functions aren't
+    /// attributed to packages, and `--api` has nothing to report.
+    #[arg(long, num_args = 1.., conflicts_with = "cargo")]
+    from_bitcode: Vec<PathBuf>,
+
     /// Path to the function capability mapping.
+    ///
+    /// This is a JSON object mapping function names to their capabilities. Functions may also be
+    /// grouped by crate name and semver range under a `crates` key, in which case they only apply
+    /// when a matching version of that crate is part of the build.
     #[arg(long)]
     function_caps: PathBuf,
 
+    /// Build only the library.
+    #[arg(long)]
+    lib: bool,
+
+    /// Build in a fresh temporary target directory and reparse every bitcode file, rather than
+    /// reusing the results of previous runs.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Do not activate the `default` feature of the selected packages.
+    #[arg(long)]
+    no_default_features: bool,
+
     /// Package to build.
     #[arg(short, long)]
     package: Option<OsString>,
 
+    /// Build artifacts with the specified profile.
+    #[arg(long, conflicts_with = "release")]
+    profile: Option<String>,
+
+    /// Which call graph edges transitive capabilities are propagated along.
+    ///
+    /// `may` follows every edge, including speculative calls through function pointers and trait
+    /// objects, while `must` only follows direct calls.
+    #[arg(long, value_enum, default_value_t = Propagation::May)]
+    propagation: Propagation,
+
     /// Build artifacts in release mode.
     #[arg(short, long)]
     release: bool,
 
     /// Rust toolchain to use.
     ///
-    /// This is mostly relevant in terms of the LLVM version.
+    /// This is mostly relevant in terms of the LLVM version, which must not be newer than the LLVM
+    /// version cargo-capslock was built against.
     #[arg(long, default_value = "1.86.0")]
     rust_toolchain: String,
 
+    /// Build for the target triple.
+    #[arg(long)]
+    target: Option<String>,
+
+    /// Build all test targets.
+    #[arg(long)]
+    tests: bool,
+
     /// Build all packages in the workspace.
     #[arg(long)]
     workspace: bool,
@@ -48,65 +178,260 @@ pub struct Static {
     path: Option<PathBuf>,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Format {
+    Json,
+    Table,
+}
+
 impl Static {
     #[tracing::instrument(err)]
     pub fn main(self) -> anyhow::Result<()> {
         let function_caps = FunctionCaps::from_path(&self.function_caps)?;
 
-        // Set up a temporary target directory so that we don't have to worry about
-        // cross-contamination, and we know exactly which `.bc` files are relevant.
-        let target = TempDir::new()?;
-
-        // Build the package.
-        self.build(target.path())?;
-
-        // Process the generated bitcode files.
-        let mut builder = bitcode::Builder::new(PathBuf::new(), &function_caps);
-        for path_result in WalkDir::new(
-            target
-                .path()
-                .join(if self.release { "release" } else { "debug" })
-                .join("deps"),
-        )
-        .into_iter()
-        .filter_map_ok(|entry| {
-            if entry.file_type().is_file()
-                && entry
-                    .path()
-                    .extension()
-                    .is_some_and(|ext| ext.as_bytes() == b"bc")
-            {
-                Some(entry.into_path())
-            } else {
+        let cache = match (
+            self.no_cache,
+            self.cache_dir.clone().or_else(cache::Cache::default_dir),
+        ) {
+            (false, Some(dir)) => Some(cache::Cache::new(dir)?),
+            (false, None) => {
+                tracing::warn!("cannot find a cache directory; caching is disabled");
                 None
             }
-        }) {
-            builder.add_module(path_result?)?;
+            (true, _) => None,
+        };
+
+        if self.feature_matrix {
+            let matrix = self.feature_matrix(&function_caps, cache.as_ref())?;
+            match self.format {
+                Format::Json => {
+                    serde_json::to_writer_pretty(std::io::stdout(), &matrix)?;
+                    println!();
+                }
+                Format::Table => table::write_features(std::io::stdout(), &matrix)?,
+            }
+
+            return Ok(());
         }
 
-        serde_json::to_writer_pretty(std::io::stdout(), &builder.into_report())?;
-        println!();
+        let report = self.analyse(&function_caps, cache.as_ref())?;
+        match self.format {
+            Format::Json => {
+                serde_json::to_writer_pretty(std::io::stdout(), &report)?;
+                println!();
+            }
+            Format::Table if self.api => table::write_api(std::io::stdout(), &report.process)?,
+            Format::Table => table::write(std::io::stdout(), &report.process)?,
+        }
 
         Ok(())
     }
 
+    /// Builds the bitcode, unless it was given, and analyses it.
+    fn analyse(
+        &self,
+        function_caps: &FunctionCaps,
+        cache: Option<&cache::Cache>,
+    ) -> anyhow::Result<Report> {
+        // With a cache, we reuse a target directory per workspace so that Cargo only rebuilds
+        // what's changed, and filter out stale `.bc` files once we know what was built. Without
+        // one, we set up a temporary target directory so that we don't have to worry about
+        // cross-contamination, and we know exactly which `.bc` files are relevant.
+        //
+        // There's nothing to build if we were given bitcode.
+        let target = match cache {
+            _ if !self.from_bitcode.is_empty() => None,
+            Some(cache) => Some(Target::Persistent(
+                cache.target_dir(&self.workspace_path()?)?,
+            )),
+            None => Some(Target::Temporary(TempDir::new()?)),
+        };
+
+        let (artifacts, paths) = match &target {
+            Some(target) => {
+                // Make sure we'll be able to parse the bitcode before spending time building it.
+                let toolchain =
+                    toolchain::Toolchain::detect(&self.rust_toolchain, &self.workspace_path()?)?;
+                toolchain.check_llvm()?;
+
+                // Build the package.
+                let artifacts = self.build(target.path(), &toolchain)?;
+
+                // Examples and build scripts are the only targets that Cargo doesn't build into
+                // `deps`.
+                let profile_dir = self.profile_dir(target.path());
+                let mut paths = bitcode_files(&profile_dir.join("deps"))?;
+                if self.examples {
+                    paths.extend(bitcode_files(&profile_dir.join("examples"))?);
+                }
+                paths.extend(build_script_files(&profile_dir.join("build"))?);
+                if self.target.is_some() {
+                    tracing::warn!(
+                        "RUSTFLAGS don't apply to build scripts or proc-macros when a target is \
+                         given, so they won't be analysed"
+                    );
+                }
+
+                // Even a fresh target directory can contain bitcode for units that aren't part of
+                // the build we asked for, so we only analyse the units Cargo reported.
+                let paths = artifacts.current(paths);
+                for unit in artifacts.without_bitcode(&paths) {
+                    // We've already warned that host code isn't compiled to bitcode when a target
+                    // is given.
+                    if self.target.is_some()
+                        && matches!(unit.kind, cargo::Kind::BuildScript | cargo::Kind::ProcMacro)
+                    {
+                        continue;
+                    }
+                    if let Some(package) = artifacts.packages().get(unit.package) {
+                        tracing::warn!(
+                            package = %package.name,
+                            version = %package.version,
+                            kind = ?unit.kind,
+                            "no bitcode was produced, so this code won't be analysed"
+                        );
+                    }
+                }
+
+                (artifacts, paths)
+            }
+            None => {
+                // Without Cargo, we have no way of knowing which package each module belongs to.
+                let mut paths = Vec::new();
+                for path in &self.from_bitcode {
+                    if path.is_dir() {
+                        paths.extend(bitcode_files(path)?);
+                    } else {
+                        paths.push(path.clone());
+                    }
+                }
+
+                (cargo::Artifacts::default(), paths)
+            }
+        };
+
+        // Report the executable if there's an obvious one, and the workspace if not.
+        let path = match artifacts.executable() {
+            Some(executable) => executable.to_path_buf(),
+            None => self.workspace_path()?.into_owned(),
+        };
+
+        // Process the bitcode files.
+        let mut builder = bitcode::Builder::new(path, function_caps, &artifacts)
+            .with_api(self.api)
+            .with_propagation(self.propagation)
+            .with_call_graph_options(CallGraphOptions {
+                address_taken_only: self.address_taken_only,
+            })
+            .with_cache(cache);
+        if target.is_some() {
+            builder = builder
+                .with_build_config(self.build_config())
+                .with_dependencies(self.dependencies()?);
+        }
+        if let Some(Target::Temporary(dir)) = &target {
+            builder = builder.with_temporary_dir(dir.path());
+        }
+        builder.add_modules(paths)?;
+
+        Ok(builder.into_report())
+    }
+
+    /// Analyses the selected package with no features, and then with each of its features.
+    ///
+    /// The capabilities of each configuration include those of any build scripts and proc-macros,
+    /// since enabling a feature can pull those in just as it can pull in libraries.
     #[tracing::instrument(skip_all, err)]
-    fn build(&self, target: &Path) -> anyhow::Result<()> {
+    fn feature_matrix(
+        &self,
+        function_caps: &FunctionCaps,
+        cache: Option<&cache::Cache>,
+    ) -> anyhow::Result<report::FeatureMatrix> {
+        let metadata = metadata::Metadata::load(
+            &self.workspace_path()?,
+            &self.rust_toolchain,
+            Vec::<String>::new(),
+        )?;
+        let package = features::PackageFeatures::new(&metadata, self.package_spec())?;
+
+        // Cargo only allows the features of workspace members to be selected, so anything else is
+        // built through a wrapper package that depends on it and forwards each of its features.
+        // With a cache, the wrapper is kept alongside the workspace's target directory, so that
+        // its own target directory is reused between runs.
+        let _wrapper_temp;
+        let base = if package.member {
+            Self {
+                package: Some(package.spec().into()),
+                ..self.clone()
+            }
+        } else {
+            let dir = match cache {
+                Some(cache) => cache
+                    .target_dir(&self.workspace_path()?)?
+                    .join("feature-matrix")
+                    .join(package.spec()),
+                None => {
+                    let temp = TempDir::new()?;
+                    let dir = temp.path().to_path_buf();
+                    _wrapper_temp = temp;
+                    dir
+                }
+            };
+            package.write_wrapper(&dir, &metadata.workspace_root)?;
+
+            Self {
+                path: Some(dir),
+                package: None,
+                workspace: false,
+                bin: None,
+                lib: false,
+                examples: false,
+                tests: false,
+                ..self.clone()
+            }
+        };
+
+        let capabilities = |features: Vec<String>| -> anyhow::Result<_> {
+            tracing::info!(package = %package.spec(), ?features, "building");
+            let config = Self {
+                features,
+                no_default_features: true,
+                ..base.clone()
+            };
+
+            let report = config.analyse(function_caps, cache)?;
+            Ok(report
+                .children
+                .into_iter()
+                .fold(report.process.capabilities, |mut caps, child| {
+                    caps.extend(child.capabilities);
+                    caps
+                }))
+        };
+
+        let baseline = capabilities(Vec::new())?;
+        let by_feature = package
+            .features
+            .iter()
+            .map(|feature| Ok((feature.clone(), capabilities(vec![feature.clone()])?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(features::matrix(package.package, baseline, by_feature))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    fn build(
+        &self,
+        target: &Path,
+        toolchain: &toolchain::Toolchain,
+    ) -> anyhow::Result<cargo::Artifacts> {
         let mut cargo = CargoBuild::new()
             // This is the key: we need to emit an LLVM bitcode file.
             //
             // We're also going to disable inlining as much as possible to get
             // the most representative call graph, which involves some secret
-            // switches.
-            //
-            // FIXME: some of these flags have changed over time; we should
-            // detect the toolchain version and convert, say, the
-            // inline-threshold flag into -Cinline-threshold on older rustc
-            // versions.
-            .env(
-                "RUSTFLAGS",
-                "--emit=llvm-bc -Cno-prepopulate-passes -Cllvm-args=--inline-threshold=999999999 -Zinline-mir=no",
-            )
+            // switches that vary by toolchain.
+            .env("RUSTFLAGS", toolchain.rustflags())
             .env("RUSTC_BOOTSTRAP", "1")
             // Control the Rust version (which indirectly controls the LLVM version, which is really
             // what we care about).
@@ -116,32 +441,335 @@ impl Static {
         if let Some(bin) = &self.bin {
             cargo = cargo.bin(bin);
         }
+        if self.examples {
+            cargo = cargo.examples();
+        }
+        if self.lib {
+            cargo = cargo.arg("--lib");
+        }
+        if self.tests {
+            cargo = cargo.tests();
+        }
         if let Some(package) = &self.package {
             cargo = cargo.package(package);
         }
+        if self.workspace {
+            cargo = cargo.arg("--workspace");
+        }
+        if !self.features.is_empty() {
+            cargo = cargo.features(self.features.join(","));
+        }
+        if self.all_features {
+            cargo = cargo.all_features();
+        }
+        if self.no_default_features {
+            cargo = cargo.no_default_features();
+        }
         if self.release {
             cargo = cargo.release();
         }
-        if self.workspace {
-            cargo = cargo.arg("--workspace");
+        if let Some(profile) = &self.profile {
+            cargo = cargo.arg("--profile").arg(profile);
+        }
+        if let Some(target) = &self.target {
+            cargo = cargo.target(target);
         }
 
-        let path = match &self.path {
-            Some(path) => Cow::Borrowed(path),
-            None => Cow::Owned(std::env::current_dir()?),
-        };
+        let path = self.workspace_path()?;
 
-        // We can't set the working directory using escargot, so we'll get the underlying `Command`,
-        // mutate that, and then pass it back to `CommandMessages`.
+        // We can't set the working directory using escargot, so we'll get the underlying `Command`
+        // and run it ourselves. Cargo's own output goes straight to stderr, while its JSON
+        // messages are read from stdout.
         let mut cmd = cargo.into_command();
-        cmd.current_dir(path.as_path());
+        cmd.current_dir(&path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        let mut child = cmd.spawn().context("cannot run cargo")?;
+        let stdout = BufReader::new(child.stdout.take().expect("piped above"));
 
-        // We have to iterate the messages for Cargo to progress.
-        for msg_result in CommandMessages::with_command(cmd)? {
-            let msg = msg_result?;
+        // We have to iterate the messages for Cargo to progress, but we'll also take the
+        // opportunity to find out which packages the artifacts belong to, and to pass on any
+        // diagnostics, since Cargo doesn't print them when it's emitting JSON.
+        let mut artifacts = cargo::Artifacts::default();
+        let mut errors = 0;
+        let mut unparseable = 0;
+        let mut success = None;
+        for line in stdout.lines() {
+            let line = line?;
+            let msg = match serde_json::from_str(&line) {
+                Ok(msg) => msg,
+                // A message we can't parse may have been an artifact, so we can't trust that we
+                // know what was built, but we'll keep reading so that Cargo can finish.
+                Err(e) if line.starts_with('{') => {
+                    tracing::warn!(%e, %line, "cannot parse cargo message");
+                    unparseable += 1;
+                    continue;
+                }
+                Err(e) => {
+                    tracing::debug!(%e, %line, "ignoring non-JSON cargo output");
+                    continue;
+                }
+            };
             tracing::trace!(?msg, "cargo message");
+
+            match msg {
+                cargo::Message::CompilerArtifact(artifact) => artifacts.insert(artifact),
+                cargo::Message::CompilerMessage(msg) => {
+                    if msg.message.level == "error" {
+                        errors += 1;
+                    }
+                    if let Some(rendered) = msg.message.rendered {
+                        eprint!("{rendered}");
+                    }
+                }
+                cargo::Message::BuildFinished(finished) => success = Some(finished.success),
+                cargo::Message::Other => {}
+            }
+        }
+
+        let status = child.wait()?;
+        match success {
+            Some(false) => Err(cargo::BuildError::Compile { errors }.into()),
+            _ if !status.success() => Err(cargo::BuildError::Cargo(status).into()),
+            _ if unparseable > 0 => Err(cargo::BuildError::Messages { count: unparseable }.into()),
+            _ => Ok(artifacts),
+        }
+    }
+
+    /// Resolves the dependencies of the selected packages with the selected features.
+    fn dependencies(&self) -> anyhow::Result<metadata::Dependencies> {
+        let mut args = Vec::new();
+        if !self.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+        if self.all_features {
+            args.push("--all-features".into());
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".into());
+        }
+        if let Some(target) = &self.target {
+            args.push("--filter-platform".into());
+            args.push(target.clone());
+        }
+
+        let metadata =
+            metadata::Metadata::load(&self.workspace_path()?, &self.rust_toolchain, args)?;
+        let roots = match self.package_spec() {
+            Some(spec) => vec![metadata.find(spec)?.id.clone()],
+            None if self.workspace => metadata.workspace_members.clone(),
+            None => metadata.default_members(),
+        };
+
+        Ok(metadata::Dependencies::new(
+            &metadata,
+            &roots,
+            self.tests || self.examples,
+        ))
+    }
+
+    fn package_spec(&self) -> Option<&str> {
+        self.package.as_ref().and_then(|package| package.to_str())
+    }
+
+    /// Returns the Cargo options used for the build, for the report's metadata.
+    fn build_config(&self) -> report::BuildConfig {
+        report::BuildConfig {
+            profile: self.profile().to_string(),
+            target: self.target.clone(),
+            package: self
+                .package
+                .as_ref()
+                .map(|package| package.to_string_lossy().into_owned()),
+            workspace: self.workspace,
+            features: self.features.clone(),
+            all_features: self.all_features,
+            no_default_features: self.no_default_features,
+            lib: self.lib,
+            bin: self
+                .bin
+                .as_ref()
+                .map(|bin| bin.to_string_lossy().into_owned()),
+            tests: self.tests,
+            examples: self.examples,
+        }
+    }
+
+    fn profile(&self) -> &str {
+        match &self.profile {
+            Some(profile) => profile,
+            None if self.release => "release",
+            None => "dev",
+        }
+    }
+
+    /// Returns the directory within the target directory that Cargo writes the artifacts for the
+    /// selected profile and target triple to.
+    fn profile_dir(&self, target: &Path) -> PathBuf {
+        let mut dir = target.to_path_buf();
+        if let Some(triple) = &self.target {
+            dir.push(triple);
+        }
+
+        // The built-in profiles don't use their own names for their directories.
+        dir.push(match self.profile() {
+            "dev" | "test" => "debug",
+            "bench" => "release",
+            profile => profile,
+        });
+
+        dir
+    }
+
+    fn workspace_path(&self) -> std::io::Result<Cow<'_, Path>> {
+        Ok(match &self.path {
+            Some(path) => Cow::Borrowed(path.as_path()),
+            None => Cow::Owned(std::env::current_dir()?),
+        })
+    }
+}
+
+/// The target directory Cargo builds into.
+enum Target {
+    /// A target directory shared with previous runs.
+    Persistent(PathBuf),
+    /// A target directory that's removed when dropped.
+    Temporary(TempDir),
+}
+
+impl Target {
+    fn path(&self) -> &Path {
+        match self {
+            Self::Persistent(path) => path,
+            Self::Temporary(dir) => dir.path(),
         }
+    }
+}
+
+/// Finds the LLVM bitcode and textual IR files within the given directory.
+///
+/// Where there's both bitcode and textual IR for the same module, only the bitcode is returned,
+/// since it's quicker to parse and analysing both would count everything in the module twice.
+fn bitcode_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let paths = WalkDir::new(dir)
+        .into_iter()
+        .filter_map_ok(|entry| {
+            if entry.file_type().is_file()
+                && entry
+                    .path()
+                    .extension()
+                    .is_some_and(|ext| ext.as_bytes() == b"bc" || ext.as_bytes() == b"ll")
+            {
+                Some(entry.into_path())
+            } else {
+                None
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let bitcode = paths
+        .iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext.as_bytes() == b"bc"))
+        .cloned()
+        .collect::<HashSet<_>>();
+    Ok(paths
+        .into_iter()
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext.as_bytes() == b"bc")
+                || !bitcode.contains(&path.with_extension("bc"))
+        })
+        .collect())
+}
+
+/// Finds the bitcode for build scripts, which Cargo builds into a directory of their own within
+/// the given directory, alongside the directories they write their output to.
+fn build_script_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(WalkDir::new(dir)
+        .min_depth(2)
+        .max_depth(2)
+        .into_iter()
+        .filter_map_ok(|entry| {
+            let path = entry.path();
+            if entry.file_type().is_file()
+                && path.extension().is_some_and(|ext| ext.as_bytes() == b"bc")
+                && path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(|stem| stem.starts_with("build_script_"))
+            {
+                Some(entry.into_path())
+            } else {
+                None
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitcode_and_ir() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for file in ["foo.bc", "foo.ll", "bar.ll", "baz.o"] {
+            std::fs::write(dir.path().join(file), "")?;
+        }
+
+        let files = bitcode_files(dir.path())?
+            .into_iter()
+            .map(|path| path.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("bar.ll"), PathBuf::from("foo.bc")]
+        );
 
         Ok(())
     }
+
+    #[test]
+    fn profile_dir() {
+        let profile_dir = |args: &[&str]| {
+            let args = ["static", "--function-caps", "caps.json"]
+                .iter()
+                .chain(args.iter());
+            Static::try_parse_from(args)
+                .unwrap()
+                .profile_dir(Path::new("target"))
+        };
+
+        assert_eq!(profile_dir(&[]), Path::new("target/debug"));
+        assert_eq!(
+            profile_dir(&["--profile", "dev"]),
+            Path::new("target/debug")
+        );
+        assert_eq!(profile_dir(&["--release"]), Path::new("target/release"));
+        assert_eq!(
+            profile_dir(&["--profile", "release"]),
+            Path::new("target/release")
+        );
+        assert_eq!(
+            profile_dir(&["--profile", "test"]),
+            Path::new("target/debug")
+        );
+        assert_eq!(
+            profile_dir(&["--profile", "bench"]),
+            Path::new("target/release")
+        );
+        assert_eq!(
+            profile_dir(&["--profile", "fuzz"]),
+            Path::new("target/fuzz")
+        );
+        assert_eq!(
+            profile_dir(&["--profile", "dev", "--target", "aarch64-unknown-linux-gnu"]),
+            Path::new("target/aarch64-unknown-linux-gnu/debug")
+        );
+    }
 }
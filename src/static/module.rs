@@ -0,0 +1,105 @@
+use std::collections::{HashMap, HashSet};
+
+use capslock::{Capability, report};
+use itertools::Itertools;
+use llvm_ir_analysis::{
+    ModuleSummary,
+    llvm_ir::{Module, module::Linkage},
+    referenced_functions,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    function::ToFunction,
+    r#static::{
+        env::environ_functions, evidence::constant_arguments, pointer::unsafe_pointer_functions,
+    },
+};
+
+/// Everything we need from a bitcode module, independent of the build it was part of.
+///
+/// Parsing bitcode is by far the most expensive part of the analysis, so this is what gets cached
+/// for each module: anything that depends on the rest of the build, such as which package the
+/// module belongs to or which versioned function capabilities apply, is worked out afresh on each
+/// run.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModuleInfo {
+    /// Every function defined or declared in the module.
+    pub functions: Vec<FunctionInfo>,
+    /// Defined functions referenced from global initializers, which is how the test harness finds
+    /// each test.
+    pub referenced: Vec<String>,
+    /// Defined functions that use pointers unsafely, which directly have
+    /// `CAPABILITY_UNSAFE_POINTER`.
+    pub unsafe_pointer: Vec<String>,
+    /// Defined functions that access the process environment through `environ`, along with the
+    /// capability each access implies.
+    pub environ: Vec<(String, Capability)>,
+    /// Constant arguments passed to capability-relevant calls, by the mangled name of the caller.
+    pub evidence: Vec<(String, report::Evidence)>,
+    /// What's needed to build the cross-module call graph.
+    pub summary: ModuleSummary,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FunctionInfo {
+    pub mangled: String,
+    /// The function, without any capabilities.
+    pub function: report::Function,
+    pub defined: bool,
+    /// Whether the function has external linkage.
+    pub external: bool,
+}
+
+impl ModuleInfo {
+    #[tracing::instrument(skip_all, err)]
+    pub fn new(module: &Module) -> anyhow::Result<Self> {
+        let mut functions =
+            Vec::with_capacity(module.functions.len() + module.func_declarations.len());
+        for func in module.functions.iter() {
+            functions.push(FunctionInfo {
+                mangled: func.name.clone(),
+                function: func.to_function()?,
+                defined: true,
+                external: func.linkage == Linkage::External,
+            });
+        }
+        for func in module.func_declarations.iter() {
+            functions.push(FunctionInfo {
+                mangled: func.name.clone(),
+                function: func.to_function()?,
+                defined: false,
+                external: func.linkage == Linkage::External,
+            });
+        }
+
+        let mut referenced = HashSet::new();
+        for init in module
+            .global_vars
+            .iter()
+            .filter_map(|var| var.initializer.as_ref())
+        {
+            referenced_functions(init, &mut referenced);
+        }
+
+        let display_names = functions
+            .iter()
+            .map(|func| (func.mangled.as_str(), func.function.display_name()))
+            .collect::<HashMap<_, _>>();
+        let unsafe_pointer = unsafe_pointer_functions(module, &display_names)
+            .map(String::from)
+            .collect();
+        let evidence = constant_arguments(module, &display_names);
+
+        Ok(Self {
+            functions,
+            referenced: referenced.into_iter().sorted().map(String::from).collect(),
+            unsafe_pointer,
+            environ: environ_functions(module)
+                .map(|(name, cap)| (name.to_string(), cap))
+                .collect(),
+            evidence,
+            summary: ModuleSummary::new(module),
+        })
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use llvm_ir_analysis::llvm_ir::{
+    Constant, Function, Instruction, Module, Name, Operand, Terminator,
+};
+
+use crate::r#static::entry::{is_function, is_in_crate};
+
+/// Functions that read or write through a raw pointer, which is only unsafe if the pointer didn't
+/// come from something the caller owns.
+const RAW_ACCESS: &[&str] = &[
+    "core::ptr::read",
+    "core::ptr::read_unaligned",
+    "core::ptr::read_volatile",
+    "core::ptr::write",
+    "core::ptr::write_unaligned",
+    "core::ptr::write_volatile",
+];
+
+/// Functions that reinterpret memory regardless of their arguments.
+///
+/// `core::mem::transmute` itself is an intrinsic, so it never appears in bitcode: transmutes
+/// between integers and pointers are caught by the casts they're lowered to instead.
+const REINTERPRET: &[&str] = &["core::mem::transmute_copy"];
+
+/// The crates whose own use of raw pointers we don't report, since they're what every other crate
+/// is built on.
+const STD_CRATES: &[&str] = &["core", "alloc", "std"];
+
+/// Returns the defined functions in the module that use pointers in the same ways as Go's
+/// `unsafe.Pointer`: casting between pointers and integers, reinterpreting memory, or accessing
+/// memory through a pointer that isn't derived from a local, a global or an argument.
+///
+/// `display_names` maps each function's mangled name to its display name.
+pub fn unsafe_pointer_functions<'m>(
+    module: &'m Module,
+    display_names: &HashMap<&str, &str>,
+) -> impl Iterator<Item = &'m str> {
+    module
+        .functions
+        .iter()
+        .filter(|func| {
+            display_names.get(func.name.as_str()).is_none_or(|name| {
+                !STD_CRATES
+                    .iter()
+                    .any(|crate_name| is_in_crate(name, crate_name))
+            })
+        })
+        .filter(|func| uses_unsafe_pointer(func, display_names))
+        .map(|func| func.name.as_str())
+}
+
+fn uses_unsafe_pointer(func: &Function, display_names: &HashMap<&str, &str>) -> bool {
+    let defs = func
+        .basic_blocks
+        .iter()
+        .flat_map(|bb| bb.instrs.iter())
+        .filter_map(|inst| Some((inst.try_get_result()?, inst)))
+        .collect::<HashMap<_, _>>();
+    let calls = func.basic_blocks.iter().flat_map(|bb| {
+        let invoke = match &bb.term {
+            Terminator::Invoke(invoke) => Some((invoke.function.as_ref(), &invoke.arguments)),
+            _ => None,
+        };
+        bb.instrs
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Call(call) => Some((call.function.as_ref(), &call.arguments)),
+                _ => None,
+            })
+            .chain(invoke)
+    });
+
+    for inst in func.basic_blocks.iter().flat_map(|bb| bb.instrs.iter()) {
+        let casts = match inst {
+            Instruction::IntToPtr(_) | Instruction::PtrToInt(_) => true,
+            Instruction::Load(load) => has_cast(&load.address),
+            Instruction::Store(store) => has_cast(&store.address) || has_cast(&store.value),
+            Instruction::GetElementPtr(gep) => has_cast(&gep.address),
+            _ => false,
+        };
+        if casts {
+            return true;
+        }
+    }
+
+    for (callee, arguments) in calls {
+        if arguments.iter().any(|(arg, _)| has_cast(arg)) {
+            return true;
+        }
+
+        let Some(callee) = callee.right().and_then(callee_name) else {
+            continue;
+        };
+        let Some(callee) = display_names.get(callee) else {
+            continue;
+        };
+        if REINTERPRET.iter().any(|path| is_function(callee, path)) {
+            return true;
+        }
+        if RAW_ACCESS.iter().any(|path| is_function(callee, path))
+            && let Some((pointer, _)) = arguments.first()
+            && !is_derived(pointer, &defs)
+        {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Returns the name of the function that a call is made to, if it's a direct call.
+pub fn callee_name(callee: &Operand) -> Option<&str> {
+    match callee {
+        Operand::ConstantOperand(constant) => match constant.as_ref() {
+            Constant::GlobalReference {
+                name: Name::Name(name),
+                ..
+            } => Some(name.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns true if the operand is, or contains, a constant cast between a pointer and an integer.
+fn has_cast(operand: &Operand) -> bool {
+    match operand {
+        Operand::ConstantOperand(constant) => constant_has_cast(constant),
+        _ => false,
+    }
+}
+
+fn constant_has_cast(constant: &Constant) -> bool {
+    match constant {
+        Constant::IntToPtr(_) | Constant::PtrToInt(_) => true,
+        Constant::BitCast(cast) => constant_has_cast(&cast.operand),
+        Constant::AddrSpaceCast(cast) => constant_has_cast(&cast.operand),
+        Constant::GetElementPtr(gep) => constant_has_cast(&gep.address),
+        _ => false,
+    }
+}
+
+/// Returns true if the pointer is derived, through casts and offsets, from a local allocation, a
+/// global, or one of the function's arguments.
+///
+/// Arguments are the caller's to vouch for: references are passed as pointers, so anything a
+/// caller does to make up a pointer is caught in the caller instead. Pointers from anywhere else,
+/// such as loads, calls or phis, can't be vouched for. Since phis aren't followed, this can't loop.
+fn is_derived(pointer: &Operand, defs: &HashMap<&Name, &Instruction>) -> bool {
+    match pointer {
+        Operand::LocalOperand { name, .. } => match defs.get(name) {
+            // Every local that isn't the result of an instruction is an argument.
+            None => true,
+            Some(Instruction::Alloca(_)) => true,
+            Some(Instruction::GetElementPtr(gep)) => is_derived(&gep.address, defs),
+            Some(Instruction::BitCast(cast)) => is_derived(&cast.operand, defs),
+            Some(Instruction::AddrSpaceCast(cast)) => is_derived(&cast.operand, defs),
+            _ => false,
+        },
+        Operand::ConstantOperand(constant) => is_derived_constant(constant),
+        Operand::MetadataOperand => false,
+    }
+}
+
+fn is_derived_constant(constant: &Constant) -> bool {
+    match constant {
+        Constant::GlobalReference { .. } => true,
+        Constant::BitCast(cast) => is_derived_constant(&cast.operand),
+        Constant::AddrSpaceCast(cast) => is_derived_constant(&cast.operand),
+        Constant::GetElementPtr(gep) => is_derived_constant(&gep.address),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsafe_pointers() {
+        let module = Module::from_bc_path("tests/bcfiles/pointer.bc").unwrap();
+        let display_names = HashMap::from([
+            ("read", "core::ptr::read::<i32>"),
+            ("write", "core::ptr::write::<i32>"),
+            ("int_to_ptr", "app::int_to_ptr"),
+            (
+                "core_without_provenance",
+                "core::ptr::without_provenance::<i32>",
+            ),
+            ("alloc_addr", "alloc::boxed::Box<T>::addr"),
+            ("std_addr", "std::os::fd::addr"),
+        ]);
+
+        let mut functions = unsafe_pointer_functions(&module, &display_names).collect::<Vec<_>>();
+        functions.sort();
+        assert_eq!(
+            functions,
+            vec!["int_to_ptr", "ptr_to_int", "read_loaded", "write_loaded"]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::BTreeSet, io::Write};
+
+use capslock::{
+    Capability,
+    report::{FeatureMatrix, Process},
+};
+use itertools::Itertools;
+
+/// Writes the per-package capability summary as a plain text table.
+pub fn write(writer: impl Write, process: &Process) -> std::io::Result<()> {
+    let header = ["PACKAGE", "VERSION", "DIRECT", "TRANSITIVE"].map(String::from);
+    let rows = process.summary().into_iter().map(|summary| {
+        let (name, version) = match summary.package.and_then(|idx| process.packages.get(idx)) {
+            Some(package) => (package.name.clone(), package.version.clone()),
+            None => ("(unattributed)".to_string(), String::new()),
+        };
+
+        [
+            name,
+            version,
+            caps(&summary.direct),
+            caps(&summary.transitive),
+        ]
+    });
+
+    write_rows(writer, std::iter::once(header).chain(rows))
+}
+
+/// Writes the capabilities of each public API function as a plain text table.
+pub fn write_api(writer: impl Write, process: &Process) -> std::io::Result<()> {
+    let header = ["FUNCTION", "CAPABILITIES"].map(String::from);
+    let rows = process
+        .api
+        .iter()
+        .filter_map(|item| {
+            process
+                .functions
+                .get(item.function)
+                .map(|func| [func.display_name().to_string(), caps(&item.capabilities)])
+        })
+        .sorted();
+
+    write_rows(writer, std::iter::once(header).chain(rows))
+}
+
+/// Writes the capabilities added by each feature as a plain text table.
+pub fn write_features(writer: impl Write, matrix: &FeatureMatrix) -> std::io::Result<()> {
+    let header = ["FEATURE", "ADDED"].map(String::from);
+    let baseline = ["(none)".to_string(), caps(&matrix.baseline)];
+    let rows = matrix
+        .features
+        .iter()
+        .map(|(feature, added)| [feature.clone(), caps(added)]);
+
+    write_rows(writer, [header, baseline].into_iter().chain(rows))
+}
+
+fn write_rows<const N: usize>(
+    mut writer: impl Write,
+    rows: impl Iterator<Item = [String; N]>,
+) -> std::io::Result<()> {
+    let rows = rows.collect::<Vec<_>>();
+    let widths = rows.iter().fold([0; N], |mut widths, row| {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+        widths
+    });
+
+    for row in rows.iter() {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .join("  ");
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+fn caps(caps: &BTreeSet<Capability>) -> String {
+    if caps.is_empty() {
+        "-".to_string()
+    } else {
+        caps.iter().join(",")
+    }
+}
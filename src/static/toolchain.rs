@@ -0,0 +1,190 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{Context, bail};
+use llvm_ir_analysis::LLVM_VERSION_MAJOR;
+use semver::Version;
+
+/// The inlining threshold we ask LLVM to use, which is high enough to disable inlining in
+/// practice.
+const INLINE_THRESHOLD: u32 = 999_999_999;
+
+/// The first rustc release to use LLVM's new pass manager by default, which ignores
+/// `-Cinline-threshold` in favour of LLVM's own `--inline-threshold` option.
+const NEW_PASS_MANAGER: Version = Version::new(1, 57, 0);
+
+/// The first rustc release to support `-Zinline-mir`.
+const INLINE_MIR: Version = Version::new(1, 55, 0);
+
+/// The rustc toolchain that will be used to build the bitcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toolchain {
+    /// The rustc release, ignoring any pre-release suffix such as `-nightly`.
+    pub rustc: Version,
+    /// The major version of the LLVM that rustc was built with, if it reported one.
+    pub llvm: Option<u32>,
+}
+
+impl Toolchain {
+    /// Queries `rustc -vV` for the given rustup toolchain, run from the given directory.
+    #[tracing::instrument(err)]
+    pub fn detect(toolchain: &str, dir: &Path) -> anyhow::Result<Self> {
+        let output = Command::new("rustc")
+            .arg("-vV")
+            .env("RUSTUP_TOOLCHAIN", toolchain)
+            .current_dir(dir)
+            .output()
+            .context("cannot run rustc")?;
+        if !output.status.success() {
+            bail!(
+                "rustc -vV failed for toolchain {toolchain}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Self::parse(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn parse(output: &str) -> anyhow::Result<Self> {
+        let mut rustc = None;
+        let mut llvm = None;
+        for (key, value) in output.lines().filter_map(|line| line.split_once(": ")) {
+            match key {
+                "release" => {
+                    let release = value.split_once('-').map_or(value, |(release, _)| release);
+                    rustc = Some(
+                        Version::parse(release)
+                            .with_context(|| format!("cannot parse rustc release {value}"))?,
+                    );
+                }
+                "LLVM version" => {
+                    let major = value.split_once('.').map_or(value, |(major, _)| major);
+                    llvm = Some(
+                        major
+                            .parse()
+                            .with_context(|| format!("cannot parse LLVM version {value}"))?,
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            rustc: rustc.context("rustc did not report its release")?,
+            llvm,
+        })
+    }
+
+    /// Checks that we can parse the bitcode this toolchain will emit.
+    ///
+    /// Bitcode from a newer LLVM than the one we were built against generally can't be read, so
+    /// that's an error. Bitcode from an older LLVM usually can, but may be missing information.
+    pub fn check_llvm(&self) -> anyhow::Result<()> {
+        match self.llvm {
+            Some(llvm) if llvm > LLVM_VERSION_MAJOR => bail!(
+                "rustc {} uses LLVM {llvm}, but cargo-capslock can only parse bitcode from LLVM \
+                 {LLVM_VERSION_MAJOR} or older; use --rust-toolchain to select an older toolchain",
+                self.rustc
+            ),
+            Some(llvm) if llvm < LLVM_VERSION_MAJOR => tracing::warn!(
+                rustc = %self.rustc,
+                llvm,
+                expected = LLVM_VERSION_MAJOR,
+                "rustc uses an older LLVM than cargo-capslock was built against; the analysis may \
+                 be incomplete"
+            ),
+            Some(_) => {}
+            None => tracing::warn!(
+                rustc = %self.rustc,
+                expected = LLVM_VERSION_MAJOR,
+                "rustc did not report its LLVM version; it may not be able to be parsed"
+            ),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the `RUSTFLAGS` needed to emit LLVM bitcode with as little inlining as possible, so
+    /// that we get the most representative call graph.
+    ///
+    /// Some of these are unstable, so `RUSTC_BOOTSTRAP` needs to be set as well.
+    pub fn rustflags(&self) -> String {
+        let mut flags = vec![
+            "--emit=llvm-bc".to_string(),
+            "-Cno-prepopulate-passes".into(),
+        ];
+
+        if self.rustc >= NEW_PASS_MANAGER {
+            flags.push(format!("-Cllvm-args=--inline-threshold={INLINE_THRESHOLD}"));
+        } else {
+            flags.push(format!("-Cinline-threshold={INLINE_THRESHOLD}"));
+        }
+
+        if self.rustc >= INLINE_MIR {
+            flags.push("-Zinline-mir=no".into());
+        }
+
+        flags.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() -> anyhow::Result<()> {
+        let toolchain = Toolchain::parse(
+            "rustc 1.86.0 (05f9846f8 2025-03-31)
+binary: rustc
+commit-hash: 05f9846f893b09a1be1fc8560e33fc3c815cfecb
+commit-date: 2025-03-31
+host: x86_64-unknown-linux-gnu
+release: 1.86.0
+LLVM version: 19.1.7
+",
+        )?;
+        assert_eq!(
+            toolchain,
+            Toolchain {
+                rustc: Version::new(1, 86, 0),
+                llvm: Some(19)
+            }
+        );
+
+        let toolchain = Toolchain::parse("release: 1.92.0-nightly\nLLVM version: 21.1.2\n")?;
+        assert_eq!(toolchain.rustc, Version::new(1, 92, 0));
+        assert_eq!(toolchain.llvm, Some(21));
+
+        let toolchain = Toolchain::parse("release: 1.86.0\n")?;
+        assert_eq!(toolchain.llvm, None);
+
+        assert!(Toolchain::parse("LLVM version: 19.1.7\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_llvm() {
+        let toolchain = |llvm| Toolchain {
+            rustc: Version::new(1, 86, 0),
+            llvm,
+        };
+        assert!(toolchain(Some(LLVM_VERSION_MAJOR)).check_llvm().is_ok());
+        assert!(toolchain(Some(LLVM_VERSION_MAJOR - 1)).check_llvm().is_ok());
+        assert!(
+            toolchain(Some(LLVM_VERSION_MAJOR + 1))
+                .check_llvm()
+                .is_err()
+        );
+        assert!(toolchain(None).check_llvm().is_ok());
+    }
+
+    #[test]
+    fn rustflags() {
+        let rustflags = |rustc| Toolchain { rustc, llvm: None }.rustflags();
+
+        insta::assert_snapshot!(rustflags(Version::new(1, 86, 0)), @"--emit=llvm-bc -Cno-prepopulate-passes -Cllvm-args=--inline-threshold=999999999 -Zinline-mir=no");
+        insta::assert_snapshot!(rustflags(Version::new(1, 56, 1)), @"--emit=llvm-bc -Cno-prepopulate-passes -Cinline-threshold=999999999 -Zinline-mir=no");
+        insta::assert_snapshot!(rustflags(Version::new(1, 54, 0)), @"--emit=llvm-bc -Cno-prepopulate-passes -Cinline-threshold=999999999");
+    }
+}
@@ -0,0 +1,38 @@
+; Hand-written to mirror the IR for direct uses of libc's `environ`. Roughly:
+;
+;     pub unsafe fn clear_env() { environ = ptr::null_mut() }
+;     pub unsafe fn first_var() -> *const c_char { *environ }
+;     pub unsafe fn count_vars() -> usize { count(addr_of!(environ)) }
+;     pub unsafe fn calls_first_var() -> *const c_char { first_var() }
+
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-unknown-linux-gnu"
+
+@environ = external global i8**
+
+define void @clear_env() {
+start:
+  store i8** null, i8*** @environ, align 8
+  ret void
+}
+
+define i8* @first_var() {
+start:
+  %vars = load i8**, i8*** @environ, align 8
+  %var = load i8*, i8** %vars, align 8
+  ret i8* %var
+}
+
+define i64 @count_vars() {
+start:
+  %n = call i64 @count(i8*** @environ)
+  ret i64 %n
+}
+
+define i8* @calls_first_var() {
+start:
+  %var = call i8* @first_var()
+  ret i8* %var
+}
+
+declare i64 @count(i8***)
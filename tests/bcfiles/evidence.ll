@@ -0,0 +1,54 @@
+; Hand-written to mirror the IR for constant string arguments. Roughly:
+;
+;     pub fn str_pair() -> Command { Command::new("git") }
+;     pub fn c_string() -> *const c_char { getenv(c"HOME".as_ptr()) }
+;     pub fn c_string_offset() -> *const c_char { getenv(c"HOME".as_ptr().add(1)) }
+;     pub fn not_constant(program: &str) -> Command { Command::new(program) }
+;     pub fn not_a_sink() { log("git") }
+;
+; rustc merges identical constants, so `"git"` is a prefix of a longer string,
+; with the length passed alongside it.
+
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-unknown-linux-gnu"
+
+%Command = type { [200 x i8] }
+
+@alloc_git = private unnamed_addr constant <{ [10 x i8] }> <{ [10 x i8] c"git status" }>, align 1
+@.str.home = private unnamed_addr constant [5 x i8] c"HOME\00", align 1
+
+define void @str_pair(%Command* sret(%Command) %out) {
+start:
+  call void @command_new(%Command* sret(%Command) %out, [0 x i8]* bitcast (<{ [10 x i8] }>* @alloc_git to [0 x i8]*), i64 3)
+  ret void
+}
+
+define i8* @c_string() {
+start:
+  %value = call i8* @getenv(i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.str.home, i64 0, i64 0))
+  ret i8* %value
+}
+
+define i8* @c_string_offset() {
+start:
+  %value = call i8* @getenv(i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.str.home, i64 0, i64 1))
+  ret i8* %value
+}
+
+define void @not_constant(%Command* sret(%Command) %out, [0 x i8]* %program.0, i64 %program.1) {
+start:
+  call void @command_new(%Command* sret(%Command) %out, [0 x i8]* %program.0, i64 %program.1)
+  ret void
+}
+
+define void @not_a_sink() {
+start:
+  call void @log([0 x i8]* bitcast (<{ [10 x i8] }>* @alloc_git to [0 x i8]*), i64 3)
+  ret void
+}
+
+declare void @command_new(%Command* sret(%Command), [0 x i8]*, i64)
+
+declare i8* @getenv(i8*)
+
+declare void @log([0 x i8]*, i64)
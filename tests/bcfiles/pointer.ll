@@ -0,0 +1,92 @@
+; Hand-written to mirror the IR that rustc emits for raw pointer use. Roughly:
+;
+;     pub fn int_to_ptr(addr: usize) -> *const i32 { addr as *const i32 }
+;     pub fn ptr_to_int(p: *const i32) -> usize { p as usize }
+;     pub fn read_loaded(pp: &*const i32) -> i32 { unsafe { ptr::read(*pp) } }
+;     pub fn write_loaded(pp: &*mut i32) { unsafe { ptr::write(*pp, 1) } }
+;     pub fn read_local() -> i32 { let x = 1; unsafe { ptr::read(&x) } }
+;     pub fn read_global() -> i32 { unsafe { ptr::read(&GLOBAL.1) } }
+;     pub fn read_argument(p: &i32) -> i32 { unsafe { ptr::read(p) } }
+;     pub fn safe(x: i32) -> i32 { x + 1 }
+;
+; along with `core`, `alloc` and `std` functions that cast between pointers
+; and integers themselves.
+
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-unknown-linux-gnu"
+
+@GLOBAL = internal global { i32, i32 } { i32 1, i32 2 }, align 4
+
+define i32* @int_to_ptr(i64 %addr) {
+start:
+  %p = inttoptr i64 %addr to i32*
+  ret i32* %p
+}
+
+define i64 @ptr_to_int(i32* %p) {
+start:
+  %addr = ptrtoint i32* %p to i64
+  ret i64 %addr
+}
+
+define i32 @read_loaded(i32** %pp) {
+start:
+  %p = load i32*, i32** %pp, align 8
+  %x = call i32 @read(i32* %p)
+  ret i32 %x
+}
+
+define void @write_loaded(i32** %pp) {
+start:
+  %p = load i32*, i32** %pp, align 8
+  call void @write(i32* %p, i32 1)
+  ret void
+}
+
+define i32 @read_local() {
+start:
+  %x = alloca i32, align 4
+  store i32 1, i32* %x, align 4
+  %y = call i32 @read(i32* %x)
+  ret i32 %y
+}
+
+define i32 @read_global() {
+start:
+  %x = call i32 @read(i32* getelementptr inbounds ({ i32, i32 }, { i32, i32 }* @GLOBAL, i64 0, i32 1))
+  ret i32 %x
+}
+
+define i32 @read_argument(i32* %p) {
+start:
+  %x = call i32 @read(i32* %p)
+  ret i32 %x
+}
+
+define i32 @safe(i32 %x) {
+start:
+  %y = add i32 %x, 1
+  ret i32 %y
+}
+
+define i32* @core_without_provenance(i64 %addr) {
+start:
+  %p = inttoptr i64 %addr to i32*
+  ret i32* %p
+}
+
+define i64 @alloc_addr(i32* %p) {
+start:
+  %addr = ptrtoint i32* %p to i64
+  ret i64 %addr
+}
+
+define i64 @std_addr(i32* %p) {
+start:
+  %addr = ptrtoint i32* %p to i64
+  ret i64 %addr
+}
+
+declare i32 @read(i32*)
+
+declare void @write(i32*, i32)